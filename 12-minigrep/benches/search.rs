@@ -0,0 +1,104 @@
+// Compares the whole-buffer search with the line by line one it replaced.
+//
+//     cargo bench
+//
+// The input is about 20 MB of generated text, the same on every run. Each search runs a few times
+// and the fastest run is reported, together with the throughput.
+use std::fs;
+use std::hint::black_box;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use minigrep::context::ContextLine;
+use minigrep::searcher::{Searcher, SearcherBuilder};
+use minigrep::sink::Sink;
+
+const SIZE: usize = 20 * 1024 * 1024;
+const RUNS: usize = 5;
+
+const WORDS: [&str; 16] = [
+    "nobody", "who", "are", "you", "too", "then", "there's", "a", "pair", "of", "us", "dreary", "somebody", "public", "tell",
+    "bog",
+];
+
+// Lines of random words from WORDS, with "frog" in about one line in a thousand
+fn generate() -> String {
+    let mut text = String::with_capacity(SIZE + 100);
+    // A linear congruential generator is random enough for this and needs no crate
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next = move || {
+        state = state.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+        (state >> 33) as usize
+    };
+
+    while text.len() < SIZE {
+        let words = 4 + next() % 8;
+        for i in 0..words {
+            if i > 0 {
+                text.push(' ');
+            }
+            text.push_str(WORDS[next() % WORDS.len()]);
+        }
+        if next() % 1000 == 0 {
+            text.push_str(" frog");
+        }
+        text.push('\n');
+    }
+    text
+}
+
+// The search before it looked at the whole buffer
+fn search_lines<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+    contents.lines().filter(|line| line.contains(query)).collect()
+}
+
+// Counts the matching lines, so printing doesn't get measured
+#[derive(Default)]
+struct Count(usize);
+
+impl Sink for Count {
+    fn line(&mut self, _path: &Path, line: &ContextLine) -> io::Result<()> {
+        self.0 += usize::from(line.is_match);
+        Ok(())
+    }
+}
+
+fn bench<F: FnMut() -> usize>(name: &str, bytes: usize, mut f: F) {
+    let mut fastest = Duration::MAX;
+    let mut lines = 0;
+    for _ in 0..RUNS {
+        let started = Instant::now();
+        lines = black_box(f());
+        fastest = fastest.min(started.elapsed());
+    }
+    let throughput = bytes as f64 / (1024.0 * 1024.0) / fastest.as_secs_f64();
+    println!("{:<40} {:>10.2?} {:>10.0} MB/s {:>8} lines", name, fastest, throughput, lines);
+}
+
+fn search_file(searcher: &Searcher, path: &Path) -> usize {
+    let mut count = Count::default();
+    searcher.search_path(path, &mut count).unwrap();
+    count.0
+}
+
+fn main() {
+    let text = generate();
+    let path = std::env::temp_dir().join(format!("minigrep-bench-{}.txt", std::process::id()));
+    fs::write(&path, &text).unwrap();
+
+    // A rare query, a common one and one that is in every line
+    for query in ["frog", "somebody", " "] {
+        println!("query {:?}", query);
+        bench("lines().filter(contains)", text.len(), || search_lines(query, &text).len());
+        bench("search", text.len(), || minigrep::search(query, &text).len());
+
+        let mapped = SearcherBuilder::new().pattern(query).build().unwrap();
+        let read = SearcherBuilder::new().pattern(query).mmap(false).build().unwrap();
+        bench("Searcher, file read line by line", text.len(), || search_file(&read, &path));
+        bench("Searcher, file mapped into memory", text.len(), || search_file(&mapped, &path));
+        println!();
+    }
+
+    fs::remove_file(&path).unwrap();
+}
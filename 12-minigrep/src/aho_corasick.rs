@@ -0,0 +1,219 @@
+// Searches for many literal patterns at once with an Aho-Corasick automaton.
+//
+// The patterns are stored in a trie. Every node also has a failure link to the node for the longest
+// proper suffix of its path that is still in the trie, so the text is read once, one character at a time,
+// whatever the number of patterns. Searching ignoring case works on case-folded characters (see casefold),
+// and a match always starts and ends on whole characters of the original text.
+use std::collections::VecDeque;
+
+use crate::casefold::{fold, Folded};
+
+const ROOT: usize = 0;
+
+#[derive(Debug, Clone, Default)]
+struct Node {
+    // Sorted by character, for binary search
+    next: Vec<(char, usize)>,
+    fail: usize,
+    // Lengths in (folded) characters of the patterns ending at this node, longest first,
+    // including the ones ending at the nodes reachable through failure links
+    lengths: Vec<usize>,
+}
+
+impl Node {
+    fn next(&self, c: char) -> Option<usize> {
+        self.next.binary_search_by_key(&c, |(key, _)| *key).ok().map(|index| self.next[index].1)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AhoCorasick {
+    nodes: Vec<Node>,
+    case_insensitive: bool,
+    // The length of the longest pattern, in characters
+    max_len: usize,
+    // An empty pattern matches everywhere
+    has_empty: bool,
+}
+
+impl AhoCorasick {
+    pub fn new<S: AsRef<str>>(patterns: &[S], case_insensitive: bool) -> AhoCorasick {
+        let mut automaton = AhoCorasick { nodes: vec![Node::default()], case_insensitive, max_len: 0, has_empty: false };
+
+        for pattern in patterns {
+            let mut node = ROOT;
+            let mut len = 0;
+            for c in pattern.as_ref().chars() {
+                for &c in automaton.fold(c).as_slice() {
+                    node = automaton.add_child(node, c);
+                    len += 1;
+                }
+            }
+            if len == 0 {
+                automaton.has_empty = true;
+            } else if !automaton.nodes[node].lengths.contains(&len) {
+                automaton.nodes[node].lengths.push(len);
+                automaton.max_len = automaton.max_len.max(len);
+            }
+        }
+
+        automaton.build_failure_links();
+        automaton
+    }
+
+    fn fold(&self, c: char) -> Folded {
+        if self.case_insensitive {
+            fold(c)
+        } else {
+            Folded::from(c)
+        }
+    }
+
+    fn add_child(&mut self, node: usize, c: char) -> usize {
+        if let Some(child) = self.nodes[node].next(c) {
+            return child;
+        }
+        let child = self.nodes.len();
+        self.nodes.push(Node::default());
+        let next = &mut self.nodes[node].next;
+        let index = next.partition_point(|(key, _)| *key < c);
+        next.insert(index, (c, child));
+        child
+    }
+
+    // Visits the trie breadth first, so the failure link of a node's parent is always known
+    fn build_failure_links(&mut self) {
+        let mut queue: VecDeque<usize> = self.nodes[ROOT].next.iter().map(|(_, child)| *child).collect();
+
+        while let Some(node) = queue.pop_front() {
+            for (c, child) in self.nodes[node].next.clone() {
+                let mut fail = self.nodes[node].fail;
+                let target = loop {
+                    if let Some(target) = self.nodes[fail].next(c) {
+                        break target;
+                    }
+                    if fail == ROOT {
+                        break ROOT;
+                    }
+                    fail = self.nodes[fail].fail;
+                };
+                self.nodes[child].fail = target;
+
+                let inherited = self.nodes[target].lengths.clone();
+                let lengths = &mut self.nodes[child].lengths;
+                lengths.extend(inherited);
+                lengths.sort_unstable_by(|a, b| b.cmp(a));
+                lengths.dedup();
+                queue.push_back(child);
+            }
+        }
+    }
+
+    fn step(&self, mut node: usize, c: char) -> usize {
+        loop {
+            if let Some(next) = self.nodes[node].next(c) {
+                return next;
+            }
+            if node == ROOT {
+                return ROOT;
+            }
+            node = self.nodes[node].fail;
+        }
+    }
+
+    // Returns the byte range of the leftmost match at or after `start`,
+    // the longest one if several patterns match there
+    pub fn find_at(&self, text: &str, start: usize) -> Option<(usize, usize)> {
+        if self.has_empty {
+            return Some((start, start));
+        }
+
+        let mut node = ROOT;
+        // The match found so far, and the number of characters read since it started
+        let mut best: Option<(usize, usize)> = None;
+        let mut since_best = 0;
+
+        for (offset, c) in text[start..].char_indices() {
+            if best.is_some() {
+                // Every match starting at or before the best one has ended by now
+                if since_best >= self.max_len {
+                    break;
+                }
+                since_best += 1;
+            }
+
+            for &c in self.fold(c).as_slice() {
+                node = self.step(node, c);
+            }
+
+            let end = start + offset + c.len_utf8();
+            // The longest pattern that starts on a character boundary starts first
+            let found = self.nodes[node].lengths.iter().find_map(|&len| self.start_of(text, start, end, len));
+            if let Some((begin, chars)) = found {
+                if best.is_none_or(|(best_begin, _)| begin <= best_begin) {
+                    best = Some((begin, end));
+                    since_best = chars;
+                }
+            }
+        }
+
+        best
+    }
+
+    // Walks back from `end` over `len` folded characters and returns where the match begins and
+    // how many characters of the text it covers, or None if it would begin inside a character
+    fn start_of(&self, text: &str, start: usize, end: usize, len: usize) -> Option<(usize, usize)> {
+        let mut remaining = len;
+        for (chars, (offset, c)) in text[start..end].char_indices().rev().enumerate() {
+            remaining = remaining.checked_sub(self.fold(c).as_slice().len())?;
+            if remaining == 0 {
+                return Some((start + offset, chars + 1));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find_all(patterns: &[&str], case_insensitive: bool, text: &str) -> Vec<(usize, usize)> {
+        let automaton = AhoCorasick::new(patterns, case_insensitive);
+        let mut matches = Vec::new();
+        let mut start = 0;
+        while let Some((begin, end)) = automaton.find_at(text, start) {
+            matches.push((begin, end));
+            start = end;
+        }
+        matches
+    }
+
+    #[test]
+    fn finds_every_pattern() {
+        let patterns = ["he", "she", "his", "hers"];
+        assert_eq!(vec![(1, 4), (7, 10)], find_all(&patterns, false, "ushers his"));
+        assert_eq!(Vec::<(usize, usize)>::new(), find_all(&patterns, false, "HERS"));
+    }
+
+    #[test]
+    fn prefers_leftmost_then_longest() {
+        assert_eq!(vec![(0, 4)], find_all(&["bc", "abcd"], false, "abcd"));
+        assert_eq!(vec![(0, 3)], find_all(&["ab", "abc"], false, "abc"));
+        assert_eq!(vec![(0, 2), (2, 3)], find_all(&["ab", "bcd", "c"], false, "abc"));
+    }
+
+    #[test]
+    fn ignores_case_on_whole_characters() {
+        assert_eq!(vec![(0, 7), (9, 13)], find_all(&["strasse", "rust"], true, "Straße TRUST"));
+        assert_eq!(vec![(2, 4), (5, 7)], find_all(&["ß", "sx"], true, "Maß ss"));
+        // 's' is only half of 'ß'
+        assert_eq!(Vec::<(usize, usize)>::new(), find_all(&["s", "sx"], true, "ßx"));
+    }
+
+    #[test]
+    fn empty_patterns() {
+        assert_eq!(None, AhoCorasick::new::<&str>(&[], false).find_at("text", 0));
+        assert_eq!(Some((2, 2)), AhoCorasick::new(&["x", ""], false).find_at("text", 2));
+    }
+}
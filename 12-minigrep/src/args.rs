@@ -0,0 +1,351 @@
+// Command-line parsing: splits the arguments into options and positional values.
+//
+// Short options can be grouped (`-in` is `-i -n`) and long options can take their value
+// after an '=' (`--color=never`). Everything after `--` is positional, even if it starts with '-'.
+// Short options are translated to their long name, so Config only has to handle one spelling.
+use std::error::Error;
+use std::fmt;
+
+use crate::filter::FILE_TYPES;
+
+pub const USAGE: &str = "\
+Usage: minigrep [OPTIONS] QUERY [FILE]...
+       minigrep [OPTIONS] -e PATTERN... [FILE]...
+       minigrep [OPTIONS] -f PATTERN_FILE [FILE]...
+       minigrep index build DIR
+
+Searches each FILE for lines containing QUERY, or any of the PATTERNs. If FILE is a directory, every file below it is searched.
+With no FILE, or when FILE is -, standard input is searched. gzip compressed input is decompressed first.
+`minigrep index build DIR` builds an index of the files in DIR, or updates it. Searching DIR afterwards
+skips the files that can't contain QUERY. To search for \"index\" in the files build and DIR instead,
+give the query after --, as in `minigrep -- index build DIR`.
+
+Options:
+  -i, --ignore-case       Ignore case distinctions in QUERY and the input
+  -s, --case-sensitive    Match case exactly, even if CASE_INSENSITIVE is set
+  -v, --invert-match      Select the lines that do not match
+  -n, --line-number       Prefix each line with its line number, the default in directories
+  -N, --no-line-number    Never prefix lines with their line number
+      --column            Prefix each matching line with the column of its first match, counted in
+                          bytes from 1, or 1 with -v. With -o, each match with its own column
+  -b, --byte-offset       Prefix each line with its byte offset in the input, counted from 0.
+                          With -o, each match with its own offset
+      --vimgrep           Print a line for every match as FILE:LINE:COLUMN:TEXT, for editors.
+                          Context options are ignored
+  -c, --count             Print the number of selected lines instead of the lines
+  -o, --only-matching     Print only the matched parts of the lines, each on a line of its own
+  -l, --files-with-matches
+                          Print only the names of the files with selected lines
+  -L, --files-without-match
+                          Print only the names of the files without selected lines
+  -m, --max-count N       Stop reading a file after N selected lines
+  -w, --word-regexp       Only match whole words
+  -U, --multiline         Let matches span several lines, e.g. with \n in a --regex.
+                          Every line of a match is printed, and ^ and $ match at each line
+  -A, --after-context N   Print N lines of context after each match
+  -B, --before-context N  Print N lines of context before each match
+  -C, --context N         Print N lines of context before and after each match
+  -j, --threads N         Search N files at the same time (default: one per CPU)
+      --color WHEN        Highlight matches: auto (default), always or never.
+                          auto colors only a terminal, and only when NO_COLOR is not set
+  -H, --with-filename     Prefix each line with its file name
+      --no-filename       Never prefix lines with the file name
+      --json              Print results as JSON Lines, one object per match
+  -e, --regexp PATTERN    Search for PATTERN, can be given several times
+  -f, --file PATTERN_FILE Search for the patterns in PATTERN_FILE, one per line
+      --regex             Treat QUERY and the PATTERNs as regular expressions
+      --fuzzy N           Also match text that is up to N edits (inserted, deleted or
+                          changed characters) away from QUERY or the PATTERNs
+  -r, --replace TEXT      Print matching lines with each match replaced by TEXT.
+                          With --regex, $1 or ${1} insert a capturing group, $0 the match and $$ a '$'
+      --in-place[=SUFFIX] Write the replacements back to the files instead of printing them.
+                          With SUFFIX, each edited file is first copied to its name plus SUFFIX
+  -t, --type TYPE         Only search files of TYPE in directories, e.g. rust for *.rs files.
+                          Can be given several times
+  -T, --type-not TYPE     Don't search files of TYPE in directories
+  -g, --glob GLOB         Only search the files in directories that match GLOB, or with !GLOB,
+                          the ones that don't. GLOB is matched like a line of a .gitignore file
+      --encoding NAME     Read the input as utf-8, utf-16le, utf-16be or latin1. By default UTF-16
+                          is detected by its byte order mark, and invalid UTF-8 is replaced with U+FFFD
+      --interactive       Browse the matches in the terminal and refine QUERY while typing.
+                          Takes a single pattern. Without FILE, the current directory is searched
+      --binary-files TYPE Binary files, with a NUL byte near the start, are searched but only
+                          \"Binary file X matches\" is printed with the default TYPE binary.
+                          without-match doesn't search them, text prints their lines like text
+  -a, --text              Search binary files like text, the same as --binary-files=text
+      --no-index          Search every file, even in a directory with an index
+      --no-mmap           Read files instead of mapping them into memory
+      --no-config         Ignore the config file named by MINIGREP_CONFIG
+  -h, --help              Print this help and exit
+      --                  Treat the remaining arguments as QUERY and FILEs
+
+Default options can be listed in the file named by the MINIGREP_CONFIG environment variable, one per line
+with its value after an '=' or a space, e.g. --context=2. Lines starting with # are comments.
+Options given on the command line override them.
+
+The exit status is 0 if a line was selected, 1 if none was, and 2 if an error occurred.
+Files that can't be read are reported and the other files are still searched.";
+
+// Short option to the long option it stands for
+const SHORT_OPTIONS: [(char, &str); 26] = [
+    ('i', "ignore-case"),
+    ('s', "case-sensitive"),
+    ('v', "invert-match"),
+    ('n', "line-number"),
+    ('N', "no-line-number"),
+    ('b', "byte-offset"),
+    ('c', "count"),
+    ('o', "only-matching"),
+    ('l', "files-with-matches"),
+    ('L', "files-without-match"),
+    ('m', "max-count"),
+    ('w', "word-regexp"),
+    ('a', "text"),
+    ('U', "multiline"),
+    ('A', "after-context"),
+    ('B', "before-context"),
+    ('C', "context"),
+    ('j', "threads"),
+    ('e', "regexp"),
+    ('f', "file"),
+    ('r', "replace"),
+    ('t', "type"),
+    ('T', "type-not"),
+    ('g', "glob"),
+    ('H', "with-filename"),
+    ('h', "help"),
+];
+
+#[derive(Debug, PartialEq)]
+pub enum ArgsError {
+    MissingQuery,
+    UnknownOption(String),
+    MissingValue(String),
+    UnexpectedValue(String),
+    InvalidValue { option: String, value: String },
+    // The option only makes sense together with another one
+    RequiresOption { option: String, required: String },
+    // The two options can't be used together
+    ConflictingOptions(String, String),
+    // The file given with -f couldn't be read
+    PatternFile { path: String, message: String },
+    // The file named by MINIGREP_CONFIG couldn't be read
+    ConfigFile { path: String, message: String },
+    // An error on a line of the config file
+    InConfigFile { path: String, line: usize, error: Box<ArgsError> },
+    // Only options can be given in the config file, the query, files and patterns can't
+    NotInConfigFile(String),
+    // The option works with a single pattern, but several were given
+    SinglePattern(String),
+    // The name given to --type or --type-not is not in the file type table
+    UnknownFileType(String),
+    // Not really an error: --help was given and the caller should print USAGE
+    Help,
+}
+
+impl fmt::Display for ArgsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArgsError::MissingQuery => write!(f, "Didn't get a query string"),
+            ArgsError::UnknownOption(option) => write!(f, "unknown option '{}'", option),
+            ArgsError::MissingValue(option) => write!(f, "option '--{}' requires a value", option),
+            ArgsError::UnexpectedValue(option) => write!(f, "option '--{}' doesn't take a value", option),
+            ArgsError::InvalidValue { option, value } => {
+                write!(f, "invalid value '{}' for option '--{}'", value, option)
+            }
+            ArgsError::RequiresOption { option, required } => {
+                write!(f, "option '--{}' requires '--{}'", option, required)
+            }
+            ArgsError::ConflictingOptions(first, second) => {
+                write!(f, "options '--{}' and '--{}' can't be used together", first, second)
+            }
+            ArgsError::PatternFile { path, message } => {
+                write!(f, "couldn't read patterns from '{}': {}", path, message)
+            }
+            ArgsError::ConfigFile { path, message } => {
+                write!(f, "couldn't read the config file '{}': {}", path, message)
+            }
+            ArgsError::InConfigFile { path, line, error } => write!(f, "{}:{}: {}", path, line, error),
+            ArgsError::NotInConfigFile(text) => {
+                write!(f, "'{}' can't be given in the config file, only options other than patterns can", text)
+            }
+            ArgsError::SinglePattern(option) => write!(f, "option '--{}' takes a single pattern", option),
+            ArgsError::UnknownFileType(name) => {
+                let names: Vec<&str> = FILE_TYPES.iter().map(|(name, _)| *name).collect();
+                write!(f, "unknown file type '{}', the known types are: {}", name, names.join(", "))
+            }
+            ArgsError::Help => write!(f, "{}", USAGE),
+        }
+    }
+}
+
+impl Error for ArgsError {}
+
+#[derive(Debug, PartialEq)]
+pub enum Arg {
+    // The long name of the option, without the leading dashes
+    Option(String),
+    Positional(String),
+}
+
+pub struct ArgParser<T: Iterator<Item = String>> {
+    args: T,
+    // The rest of a group of short options, e.g. "n" after reading 'i' from "-in"
+    shorts: Vec<char>,
+    // The value of a long option given as --name=value
+    inline_value: Option<String>,
+    last_option: String,
+    options_done: bool,
+}
+
+impl<T: Iterator<Item = String>> ArgParser<T> {
+    pub fn new(args: T) -> ArgParser<T> {
+        ArgParser { args, shorts: Vec::new(), inline_value: None, last_option: String::new(), options_done: false }
+    }
+
+    pub fn next_arg(&mut self) -> Result<Option<Arg>, ArgsError> {
+        if self.inline_value.take().is_some() {
+            return Err(ArgsError::UnexpectedValue(self.last_option.clone()));
+        }
+
+        if !self.shorts.is_empty() {
+            let c = self.shorts.remove(0);
+            return self.short_option(c).map(Some);
+        }
+
+        let arg = match self.args.next() {
+            Some(arg) => arg,
+            None => return Ok(None),
+        };
+
+        if self.options_done || arg == "-" || !arg.starts_with('-') {
+            return Ok(Some(Arg::Positional(arg)));
+        }
+        if arg == "--" {
+            self.options_done = true;
+            return self.next_arg();
+        }
+
+        if let Some(long) = arg.strip_prefix("--") {
+            let name = match long.split_once('=') {
+                Some((name, value)) => {
+                    self.inline_value = Some(String::from(value));
+                    name
+                }
+                None => long,
+            };
+            self.last_option = String::from(name);
+            return Ok(Some(Arg::Option(String::from(name))));
+        }
+
+        self.shorts = arg.chars().skip(1).collect();
+        let c = self.shorts.remove(0);
+        self.short_option(c).map(Some)
+    }
+
+    // Returns the value of the option that was just read. For a short option this is the rest
+    // of the group (-A3), otherwise it is the next argument (-A 3, --after-context 3).
+    pub fn value(&mut self) -> Result<String, ArgsError> {
+        if let Some(value) = self.inline_value.take() {
+            return Ok(value);
+        }
+        if !self.shorts.is_empty() {
+            return Ok(self.shorts.drain(..).collect());
+        }
+        self.args.next().ok_or_else(|| ArgsError::MissingValue(self.last_option.clone()))
+    }
+
+    // Returns the value of an option whose value is optional, which can only be given after an '='
+    pub fn optional_value(&mut self) -> Option<String> {
+        self.inline_value.take()
+    }
+
+    // Returns the option's value parsed into a type such as a number
+    pub fn parsed_value<V: std::str::FromStr>(&mut self) -> Result<V, ArgsError> {
+        let value = self.value()?;
+        value.parse().map_err(|_| ArgsError::InvalidValue { option: self.last_option.clone(), value })
+    }
+
+    fn short_option(&mut self, c: char) -> Result<Arg, ArgsError> {
+        match SHORT_OPTIONS.iter().find(|(short, _)| *short == c) {
+            Some((_, long)) => {
+                self.last_option = String::from(*long);
+                Ok(Arg::Option(String::from(*long)))
+            }
+            None => Err(ArgsError::UnknownOption(format!("-{}", c))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Vec<Arg>, ArgsError> {
+        let mut parser = ArgParser::new(args.iter().map(|arg| String::from(*arg)));
+        let mut result = Vec::new();
+        while let Some(arg) = parser.next_arg()? {
+            result.push(arg);
+        }
+        Ok(result)
+    }
+
+    fn option(name: &str) -> Arg {
+        Arg::Option(String::from(name))
+    }
+
+    fn positional(value: &str) -> Arg {
+        Arg::Positional(String::from(value))
+    }
+
+    #[test]
+    fn short_options_are_translated_and_can_be_grouped() {
+        let expected = vec![option("ignore-case"), option("line-number"), positional("query"), option("count")];
+        assert_eq!(Ok(expected), parse(&["-in", "query", "-c"]));
+    }
+
+    #[test]
+    fn double_dash_ends_options() {
+        let expected = vec![option("invert-match"), positional("-query"), positional("--count")];
+        assert_eq!(Ok(expected), parse(&["--invert-match", "--", "-query", "--count"]));
+    }
+
+    #[test]
+    fn single_dash_is_positional() {
+        assert_eq!(Ok(vec![positional("query"), positional("-")]), parse(&["query", "-"]));
+    }
+
+    #[test]
+    fn unknown_short_option_is_an_error() {
+        assert_eq!(Err(ArgsError::UnknownOption(String::from("-x"))), parse(&["-ix"]));
+    }
+
+    #[test]
+    fn unexpected_inline_value_is_an_error() {
+        assert_eq!(Err(ArgsError::UnexpectedValue(String::from("count"))), parse(&["--count=3"]));
+    }
+
+    #[test]
+    fn optional_values_must_be_inline() {
+        let mut parser = ArgParser::new(vec!["--in-place=.bak", "--in-place", "file"].into_iter().map(String::from));
+        assert_eq!(Ok(Some(option("in-place"))), parser.next_arg());
+        assert_eq!(Some(String::from(".bak")), parser.optional_value());
+        assert_eq!(Ok(Some(option("in-place"))), parser.next_arg());
+        assert_eq!(None, parser.optional_value());
+        assert_eq!(Ok(Some(positional("file"))), parser.next_arg());
+    }
+
+    #[test]
+    fn values_can_be_inline_or_separate() {
+        let mut parser = ArgParser::new(vec!["--max=3", "--max", "4", "--max=x", "-A5"].into_iter().map(String::from));
+        assert_eq!(Ok(Some(option("max"))), parser.next_arg());
+        assert_eq!(Ok(3), parser.parsed_value::<usize>());
+        assert_eq!(Ok(Some(option("max"))), parser.next_arg());
+        assert_eq!(Ok(String::from("4")), parser.value());
+        assert_eq!(Ok(Some(option("max"))), parser.next_arg());
+        let expected = ArgsError::InvalidValue { option: String::from("max"), value: String::from("x") };
+        assert_eq!(Err(expected), parser.parsed_value::<usize>());
+        assert_eq!(Ok(Some(option("after-context"))), parser.next_arg());
+        assert_eq!(Ok(5), parser.parsed_value::<usize>());
+    }
+}
@@ -0,0 +1,94 @@
+// Binary files, and what to do with them (--binary-files).
+//
+// Like git and grep, an input is taken to be binary when the start of its text contains a NUL
+// byte, which text hardly ever does. The check is made after decoding, since UTF-16 text is full
+// of NUL bytes. By default a binary input is still searched, but instead of its lines, which
+// would print garbage, only "Binary file X matches" is printed when it has a match. That is known
+// after the first match, so the search stops there unless the lines are counted (-c).
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::context::ContextLine;
+use crate::sink::{Sink, Stats};
+
+// How much of the start of an input is looked at
+const BINARY_CHECK_LENGTH: usize = 8000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum BinaryFiles {
+    // Search binary inputs, but only say whether they match
+    #[default]
+    Binary,
+    // Don't search binary inputs, as if they had no match
+    WithoutMatch,
+    // Search binary inputs like text
+    Text,
+}
+
+impl FromStr for BinaryFiles {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<BinaryFiles, ()> {
+        match value {
+            "binary" => Ok(BinaryFiles::Binary),
+            "without-match" => Ok(BinaryFiles::WithoutMatch),
+            "text" => Ok(BinaryFiles::Text),
+            _ => Err(()),
+        }
+    }
+}
+
+// `bytes` is the start of the decoded text, or all of it
+pub fn is_binary(bytes: &[u8]) -> bool {
+    bytes.iter().take(BINARY_CHECK_LENGTH).any(|b| *b == 0)
+}
+
+// Leaves out the lines of a binary input, and tells the sink when the input has a match instead
+pub struct BinarySink<'s> {
+    inner: &'s mut dyn Sink,
+    matched: bool,
+}
+
+impl BinarySink<'_> {
+    pub fn new(inner: &mut dyn Sink) -> BinarySink<'_> {
+        BinarySink { inner, matched: false }
+    }
+}
+
+impl Sink for BinarySink<'_> {
+    fn begin(&mut self, path: &Path) -> io::Result<()> {
+        self.inner.begin(path)
+    }
+
+    fn line(&mut self, _path: &Path, line: &ContextLine) -> io::Result<()> {
+        self.matched |= line.is_match;
+        Ok(())
+    }
+
+    fn end(&mut self, path: &Path, stats: &Stats) -> io::Result<()> {
+        if self.matched {
+            self.inner.binary_match(path)?;
+        }
+        self.inner.end(path, stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nul_bytes_at_the_start_are_binary() {
+        assert!(is_binary(b"ELF\0\0\x01"));
+        assert!(!is_binary("Rust:\nsafe, fast, productive.".as_bytes()));
+        assert!(!is_binary(&[b"a".repeat(BINARY_CHECK_LENGTH), vec![0]].concat()));
+    }
+
+    #[test]
+    fn modes_are_parsed() {
+        assert_eq!(Ok(BinaryFiles::WithoutMatch), "without-match".parse());
+        assert_eq!(Ok(BinaryFiles::Text), "text".parse());
+        assert!("hex".parse::<BinaryFiles>().is_err());
+    }
+}
@@ -0,0 +1,179 @@
+// Search over a whole buffer in memory, such as a memory-mapped file.
+//
+// Splitting the buffer into lines and searching each of them costs time on every line, even though
+// most lines don't match. For a plain substring, the buffer is searched as a whole with Finder
+// instead, and line boundaries are only looked for around the hits. Other patterns, and searches with
+// context lines, go line by line like any other reader, and so does input that isn't valid UTF-8.
+use std::io;
+
+use crate::context::ContextLine;
+use crate::finder::{memchr, memrchr, Finder};
+use crate::matcher::Matcher;
+use crate::sink::Stats;
+use crate::stream::search_reader;
+
+// Calls `output` for every matching line and its context lines, in order, like stream::search_reader
+pub fn search_buffer<F>(
+    matcher: &Matcher,
+    buffer: &[u8],
+    before: usize,
+    after: usize,
+    max_count: Option<usize>,
+    mut output: F,
+) -> io::Result<Stats>
+where
+    F: FnMut(&ContextLine) -> io::Result<()>,
+{
+    let (contents, literal) = match (std::str::from_utf8(buffer), matcher.literal()) {
+        // A hit across a line ending wouldn't be a match
+        (Ok(contents), Some(literal)) if before == 0 && after == 0 && !literal.contains(['\n', '\r']) => (contents, literal),
+        _ => return search_reader(matcher, buffer, before, after, max_count, output),
+    };
+
+    let mut stats = Stats { searches: 1, bytes_searched: buffer.len(), ..Stats::default() };
+    // Number of line endings before `counted`
+    let mut line_endings = 0;
+    let mut counted = 0;
+
+    for (start, end) in LinesWith::new(&Finder::new(literal.as_bytes()), buffer) {
+        if max_count.is_some_and(|max| stats.matched_lines >= max) {
+            break;
+        }
+        line_endings += count_line_endings(&buffer[counted..start]);
+        counted = start;
+
+        let line = &contents[start..end];
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        // The hit may not be a whole word
+        if matcher.is_match(line) {
+            stats.matched_lines += 1;
+            output(&ContextLine { number: line_endings + 1, offset: start, text: line, is_match: true })?;
+        }
+    }
+
+    stats.searches_with_match = (stats.matched_lines > 0) as usize;
+    Ok(stats)
+}
+
+fn count_line_endings(bytes: &[u8]) -> usize {
+    bytes.iter().filter(|&&byte| byte == b'\n').count()
+}
+
+// The lines of a buffer that contain the needle, as byte ranges without the '\n'.
+// Like str::lines, there is no empty line after a final line ending.
+pub struct LinesWith<'a> {
+    finder: &'a Finder,
+    haystack: &'a [u8],
+    // Start of the first line that wasn't searched yet
+    start: usize,
+}
+
+impl<'a> LinesWith<'a> {
+    pub fn new(finder: &'a Finder, haystack: &'a [u8]) -> LinesWith<'a> {
+        LinesWith { finder, haystack, start: 0 }
+    }
+}
+
+impl Iterator for LinesWith<'_> {
+    type Item = (usize, usize);
+
+    fn next(&mut self) -> Option<(usize, usize)> {
+        if self.start >= self.haystack.len() {
+            return None;
+        }
+
+        let hit = self.start + self.finder.find(&self.haystack[self.start..])?;
+        let start = memrchr(b'\n', &self.haystack[self.start..hit]).map_or(self.start, |index| self.start + index + 1);
+        let end = memchr(b'\n', &self.haystack[hit..]).map_or(self.haystack.len(), |index| hit + index);
+        self.start = end + 1;
+        Some((start, end))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+
+    const CONTENTS: &str = "\
+one
+two match
+three\r
+four matchmatch\r
+matches
+six match";
+
+    fn collect(config: Config, before: usize, after: usize) -> (Stats, Vec<(usize, usize, String)>) {
+        let matcher = Matcher::new(&Config { case_sensitive: true, ..config }).unwrap();
+        let mut lines = Vec::new();
+        let stats = search_buffer(&matcher, CONTENTS.as_bytes(), before, after, None, |line| {
+            lines.push((line.number, line.offset, String::from(line.text)));
+            Ok(())
+        }).unwrap();
+        (stats, lines)
+    }
+
+    fn config(pattern: &str) -> Config {
+        Config { patterns: vec![String::from(pattern)], ..Config::default() }
+    }
+
+    #[test]
+    fn lines_with_returns_lines_around_hits() {
+        let finder = Finder::new(b"match");
+        let lines: Vec<_> = LinesWith::new(&finder, CONTENTS.as_bytes()).map(|(start, end)| &CONTENTS[start..end]).collect();
+        assert_eq!(vec!["two match", "four matchmatch\r", "matches", "six match"], lines);
+
+        let finder = Finder::new(b"");
+        assert_eq!(CONTENTS.lines().count(), LinesWith::new(&finder, CONTENTS.as_bytes()).count());
+    }
+
+    #[test]
+    fn search_buffer_returns_matching_lines() {
+        let (stats, lines) = collect(config("match"), 0, 0);
+        assert_eq!(Stats { searches: 1, searches_with_match: 1, matched_lines: 4, bytes_searched: CONTENTS.len() }, stats);
+        let expected = vec![
+            (2, 4, String::from("two match")),
+            (4, 21, String::from("four matchmatch")),
+            (5, 38, String::from("matches")),
+            (6, 46, String::from("six match")),
+        ];
+        assert_eq!(expected, lines);
+    }
+
+    #[test]
+    fn search_buffer_agrees_with_search_reader() {
+        for (before, after) in [(0, 0), (1, 1)] {
+            let configs = [
+                config("match"),
+                Config { word_regexp: true, ..config("match") },
+                Config { invert_match: true, ..config("match") },
+                Config { regex: true, ..config("t.o") },
+            ];
+            for config in configs {
+                let matcher = Matcher::new(&Config { case_sensitive: true, ..config }).unwrap();
+                let mut expected = Vec::new();
+                search_reader(&matcher, CONTENTS.as_bytes(), before, after, Some(2), |line| {
+                    expected.push((line.number, line.offset, String::from(line.text)));
+                    Ok(())
+                }).unwrap();
+                let mut lines = Vec::new();
+                search_buffer(&matcher, CONTENTS.as_bytes(), before, after, Some(2), |line| {
+                    lines.push((line.number, line.offset, String::from(line.text)));
+                    Ok(())
+                }).unwrap();
+                assert_eq!(expected, lines);
+            }
+        }
+    }
+
+    #[test]
+    fn search_buffer_replaces_invalid_utf8() {
+        let matcher = Matcher::new(&Config { case_sensitive: true, ..config("match") }).unwrap();
+        let mut lines = Vec::new();
+        search_buffer(&matcher, b"match\n\xff\xfe\n\xffmatch", 0, 0, None, |line| {
+            lines.push((line.number, String::from(line.text)));
+            Ok(())
+        }).unwrap();
+        assert_eq!(vec![(1, String::from("match")), (3, String::from("\u{fffd}match"))], lines);
+    }
+}
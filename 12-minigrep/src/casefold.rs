@@ -0,0 +1,198 @@
+// Case-insensitive matching based on Unicode case folding.
+//
+// Lowercasing both sides is not enough: 'ß' and "SS" or 'ẞ' have no common lowercase form,
+// and lowercasing 'İ' produces two characters, which shifts every byte offset after it.
+// Instead, each character is folded on the fly into a short sequence of characters and the
+// folded sequences are compared, while offsets keep pointing into the original text.
+// Nothing is allocated per line.
+//
+// Folding follows Unicode's default (non-Turkic) full case folding closely enough for searching:
+// - 'ß', 'ẞ' and "ss" are equal, and so are ligatures such as 'ﬁ' and "fi"
+// - 'ſ' is 's', final 'ς' is 'σ', the Kelvin sign is 'k'
+// - 'İ' is 'i' followed by a combining dot above, the dotless 'ı' stays its own letter
+
+// The longest full folding of a single character is three characters long
+#[derive(Debug, Clone, Copy)]
+pub struct Folded {
+    chars: [char; 3],
+    len: usize,
+}
+
+impl Folded {
+    pub fn as_slice(&self) -> &[char] {
+        &self.chars[..self.len]
+    }
+
+    fn push(&mut self, c: char) {
+        self.chars[self.len] = c;
+        self.len += 1;
+    }
+}
+
+// A character that is not folded at all
+impl From<char> for Folded {
+    fn from(c: char) -> Folded {
+        Folded { chars: [c, '\0', '\0'], len: 1 }
+    }
+}
+
+pub fn fold(c: char) -> Folded {
+    let mut folded = Folded { chars: ['\0'; 3], len: 0 };
+
+    if c.is_ascii() {
+        folded.push(c.to_ascii_lowercase());
+        return folded;
+    }
+    // The capital sharp s uppercases to itself and lowercases to 'ß', which still folds further
+    if c == 'ẞ' {
+        folded.push('s');
+        folded.push('s');
+        return folded;
+    }
+    // The dotless i uppercases to a plain 'I', but only Turkic folding treats them as the same letter
+    if c == 'ı' {
+        folded.push(c);
+        return folded;
+    }
+
+    // Uppercasing first maps characters such as 'ß', 'ſ' and 'ς' onto the same letters as their
+    // usual forms, lowercasing afterwards gives the folded form
+    for upper in c.to_uppercase() {
+        for lower in upper.to_lowercase() {
+            if folded.len == folded.chars.len() {
+                break;
+            }
+            folded.push(lower);
+        }
+    }
+    folded
+}
+
+// Folds a character to a single character, for comparisons that can't handle expansions
+// such as the ones in the regex engine. Characters that fold to several characters are only lowercased.
+pub fn simple_fold(c: char) -> char {
+    match fold(c).as_slice() {
+        [folded] => *folded,
+        _ => {
+            let mut lower = c.to_lowercase();
+            match (lower.next(), lower.next()) {
+                (Some(lower), None) => lower,
+                _ => c,
+            }
+        }
+    }
+}
+
+// A query prepared for case-insensitive searching
+#[derive(Debug, Clone, PartialEq)]
+pub struct FoldedQuery {
+    chars: Vec<char>,
+}
+
+impl FoldedQuery {
+    pub fn new(query: &str) -> FoldedQuery {
+        let mut chars = Vec::with_capacity(query.len());
+        for c in query.chars() {
+            chars.extend_from_slice(fold(c).as_slice());
+        }
+        FoldedQuery { chars }
+    }
+
+    // Returns the byte range in `text` of the first match at or after `start`
+    pub fn find_at(&self, text: &str, start: usize) -> Option<(usize, usize)> {
+        if self.chars.is_empty() {
+            return Some((start, start));
+        }
+
+        let first = self.chars[0];
+        for (offset, c) in text[start..].char_indices() {
+            let begin = start + offset;
+            // Cheap check before walking the whole query
+            if fold(c).as_slice()[0] != first {
+                continue;
+            }
+            if let Some(end) = self.match_at(text, begin) {
+                return Some((begin, end));
+            }
+        }
+        None
+    }
+
+    // Returns the end of the match if the query matches the text starting at `begin`.
+    // Every character of the text has to be matched completely, "s" does not match half of 'ß'.
+    fn match_at(&self, text: &str, begin: usize) -> Option<usize> {
+        let mut matched = 0;
+
+        for (offset, c) in text[begin..].char_indices() {
+            let folded = fold(c);
+            let folded = folded.as_slice();
+            if !self.chars[matched..].starts_with(folded) {
+                return None;
+            }
+            matched += folded.len();
+            if matched == self.chars.len() {
+                return Some(begin + offset + c.len_utf8());
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(query: &str, text: &str) -> Option<(usize, usize)> {
+        FoldedQuery::new(query).find_at(text, 0)
+    }
+
+    #[test]
+    fn ascii_and_simple_letters() {
+        assert_eq!(Some((1, 5)), find("rUsT", "Trust me"));
+        assert_eq!(Some((0, 8)), find("ΣΟΦΟ", "σοφος"));
+        assert_eq!(None, find("rust", "rus"));
+    }
+
+    #[test]
+    fn sharp_s_matches_ss() {
+        assert_eq!(Some((0, 7)), find("STRASSE", "straße"));
+        assert_eq!(Some((0, 8)), find("straße", "STRAẞE"));
+        assert_eq!(Some((0, 7)), find("strasse", "Straße"));
+        assert_eq!(Some((4, 6)), find("ß", "Strasse"));
+    }
+
+    #[test]
+    fn a_character_is_never_matched_halfway() {
+        // 's' alone is only half of the folded 'ß'
+        assert_eq!(None, find("s", "ß"));
+        assert_eq!(None, find("fi", "ﬃ"));
+        assert_eq!(Some((0, 3)), find("ffi", "ﬃ"));
+    }
+
+    #[test]
+    fn dotted_capital_i_keeps_offsets() {
+        // 'İ' is two bytes, while its lowercase form is three
+        assert_eq!(Some((4, 10)), find("i\u{307}stan", "İ: İstanbul"));
+        assert_eq!(Some((4, 10)), find("İSTAN", "İ: İstanbul"));
+        // Its folded form has a combining dot, so a plain 'i' is not enough
+        assert_eq!(None, find("istan", "İ: İstanbul"));
+        // The dotless i is a different letter
+        assert_eq!(None, find("i", "ı"));
+    }
+
+    #[test]
+    fn other_foldings() {
+        assert_eq!(Some((0, 3)), find("k", "\u{212a}"));
+        assert_eq!(Some((0, 2)), find("s", "ſ"));
+        assert_eq!(Some((0, 2)), find("Σ", "ς"));
+    }
+
+    #[test]
+    fn simple_fold_of_single_characters() {
+        assert_eq!('ß', simple_fold('ẞ'));
+        assert_eq!('ß', simple_fold('ß'));
+        assert_eq!('s', simple_fold('ſ'));
+        assert_eq!('σ', simple_fold('ς'));
+        assert_eq!('k', simple_fold('K'));
+    }
+}
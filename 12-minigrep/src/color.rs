@@ -0,0 +1,84 @@
+// ANSI colors for the output, chosen with --color=auto|always|never.
+//
+// With `auto` the output is only colored when stdout is a terminal and the NO_COLOR
+// environment variable is not set (see https://no-color.org). An explicit --color=always
+// wins over NO_COLOR, since it was asked for on the command line.
+use std::env;
+use std::io::{self, IsTerminal};
+use std::str::FromStr;
+
+pub const MATCH: &str = "\x1b[1;31m";
+pub const PATH: &str = "\x1b[35m";
+pub const LINE_NUMBER: &str = "\x1b[32m";
+pub const SEPARATOR: &str = "\x1b[36m";
+pub const RESET: &str = "\x1b[0m";
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ColorChoice {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    // Decides what `auto` means for this process, the result is either Always or Never
+    pub fn resolve(self) -> ColorChoice {
+        let no_color = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+        self.resolve_with(io::stdout().is_terminal(), no_color)
+    }
+
+    fn resolve_with(self, is_terminal: bool, no_color: bool) -> ColorChoice {
+        match self {
+            ColorChoice::Auto if is_terminal && !no_color => ColorChoice::Always,
+            ColorChoice::Auto => ColorChoice::Never,
+            choice => choice,
+        }
+    }
+}
+
+impl FromStr for ColorChoice {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<ColorChoice, ()> {
+        match value {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => Err(()),
+        }
+    }
+}
+
+// Wraps the text in the color when `enabled` is true
+pub fn paint(text: &str, color: &str, enabled: bool) -> String {
+    if enabled {
+        format!("{}{}{}", color, text, RESET)
+    } else {
+        String::from(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn auto_depends_on_terminal_and_no_color() {
+        assert_eq!(ColorChoice::Always, ColorChoice::Auto.resolve_with(true, false));
+        assert_eq!(ColorChoice::Never, ColorChoice::Auto.resolve_with(false, false));
+        assert_eq!(ColorChoice::Never, ColorChoice::Auto.resolve_with(true, true));
+    }
+
+    #[test]
+    fn explicit_choice_wins() {
+        assert_eq!(ColorChoice::Always, ColorChoice::Always.resolve_with(false, true));
+        assert_eq!(ColorChoice::Never, ColorChoice::Never.resolve_with(true, false));
+    }
+
+    #[test]
+    fn parse_choice() {
+        assert_eq!(Ok(ColorChoice::Never), "never".parse());
+        assert_eq!(Err(()), "sometimes".parse::<ColorChoice>());
+    }
+}
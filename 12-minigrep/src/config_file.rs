@@ -0,0 +1,53 @@
+// Default options read from the file named by the MINIGREP_CONFIG environment variable.
+//
+// Each line holds an option, with its value after an '=' or a space: `--context=2`, `-C 2` and
+// `--glob !target/**` are all fine, and the value can contain spaces. Blank lines and lines starting
+// with '#' are skipped. The options are applied before the command line, so the command line
+// overrides them, e.g. `-s` turns a default `--ignore-case` off. `--no-config` skips the file.
+use std::fs;
+use std::path::Path;
+
+use crate::args::ArgsError;
+
+// Returns the arguments on each line with an option, and the number of the line
+pub fn read(path: &Path) -> Result<Vec<(usize, Vec<String>)>, ArgsError> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| ArgsError::ConfigFile { path: path.display().to_string(), message: e.to_string() })?;
+    Ok(parse(&contents))
+}
+
+fn parse(contents: &str) -> Vec<(usize, Vec<String>)> {
+    contents
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(number, line)| {
+            let args = match line.split_once(char::is_whitespace) {
+                Some((option, value)) if !option.contains('=') => {
+                    vec![String::from(option), String::from(value.trim_start())]
+                }
+                _ => vec![String::from(line)],
+            };
+            (number, args)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_are_options_with_values() {
+        let contents = "# Defaults\n--ignore-case\n\n  -C 2\n--glob  !target/**\n--replace=a b\n";
+        let strings = |values: &[&str]| values.iter().map(|value| String::from(*value)).collect::<Vec<_>>();
+        let expected = vec![
+            (2, strings(&["--ignore-case"])),
+            (4, strings(&["-C", "2"])),
+            (5, strings(&["--glob", "!target/**"])),
+            (6, strings(&["--replace=a b"])),
+        ];
+        assert_eq!(expected, parse(contents));
+    }
+}
@@ -0,0 +1,122 @@
+// Context lines around matches, like grep's -A, -B and -C options.
+//
+// Every match brings the lines before and after it along. Windows that overlap or touch are merged
+// into a single group, so a line is never printed twice. grep prints `--` between the groups.
+use crate::matcher::Matcher;
+
+#[derive(Debug, PartialEq)]
+pub struct ContextLine<'a> {
+    // Line numbers start from 1
+    pub number: usize,
+    // Byte offset of the start of the line in the input
+    pub offset: usize,
+    pub text: &'a str,
+    // false for lines that are only shown as context
+    pub is_match: bool,
+}
+
+// Returns the matching lines with up to `before` and `after` lines of context, split into groups
+// of consecutive lines
+pub fn search_context<'a>(matcher: &Matcher, contents: &'a str, before: usize, after: usize) -> Vec<Vec<ContextLine<'a>>> {
+    let lines: Vec<&str> = contents.lines().collect();
+    let mut groups: Vec<Vec<ContextLine>> = Vec::new();
+    // Index of the first line that hasn't been added to a group yet
+    let mut next = 0;
+
+    for (index, line) in lines.iter().enumerate() {
+        if !matcher.is_match(line) {
+            continue;
+        }
+
+        let start = index.saturating_sub(before).max(next);
+        let end = (index + after + 1).min(lines.len());
+
+        // Start a new group unless this window overlaps or touches the previous one
+        if groups.is_empty() || start > next {
+            groups.push(Vec::new());
+        }
+        let group = groups.last_mut().unwrap();
+
+        for (number, text) in lines.iter().enumerate().take(end).skip(start) {
+            // A later match inside the after-context of this one is added by its own iteration
+            if number > index && matcher.is_match(text) {
+                break;
+            }
+            // The lines are slices of contents, so their offset follows from their address
+            let offset = text.as_ptr() as usize - contents.as_ptr() as usize;
+            group.push(ContextLine { number: number + 1, offset, text, is_match: number == index });
+            next = number + 1;
+        }
+    }
+
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+
+    const CONTENTS: &str = "\
+one
+two match
+three
+four
+five
+six match
+seven match
+eight
+nine
+ten match";
+
+    fn numbers(before: usize, after: usize) -> Vec<Vec<(usize, bool)>> {
+        let config = Config { patterns: vec![String::from("match")], case_sensitive: true, ..Config::default() };
+        let matcher = Matcher::new(&config).unwrap();
+        search_context(&matcher, CONTENTS, before, after).iter()
+            .map(|group| group.iter().map(|line| (line.number, line.is_match)).collect())
+            .collect()
+    }
+
+    #[test]
+    fn without_context_only_adjacent_matches_share_a_group() {
+        assert_eq!(vec![vec![(2, true)], vec![(6, true), (7, true)], vec![(10, true)]], numbers(0, 0));
+    }
+
+    #[test]
+    fn after_context() {
+        let expected = vec![
+            vec![(2, true), (3, false)],
+            vec![(6, true), (7, true), (8, false)],
+            vec![(10, true)],
+        ];
+        assert_eq!(expected, numbers(0, 1));
+    }
+
+    #[test]
+    fn before_context() {
+        let expected = vec![
+            vec![(1, false), (2, true)],
+            // Line 8 directly follows line 7, so there is no gap between the groups
+            vec![(4, false), (5, false), (6, true), (7, true), (8, false), (9, false), (10, true)],
+        ];
+        assert_eq!(expected, numbers(2, 0));
+    }
+
+    #[test]
+    fn lines_know_their_offset() {
+        let config = Config { patterns: vec![String::from("match")], case_sensitive: true, ..Config::default() };
+        let matcher = Matcher::new(&config).unwrap();
+        let groups = search_context(&matcher, CONTENTS, 1, 0);
+        assert_eq!((1, 0), (groups[0][0].number, groups[0][0].offset));
+        assert_eq!((2, 4), (groups[0][1].number, groups[0][1].offset));
+    }
+
+    #[test]
+    fn touching_windows_are_merged() {
+        let expected = vec![vec![
+            (1, false), (2, true), (3, false), (4, false), (5, false), (6, true),
+            (7, true), (8, false), (9, false), (10, true),
+        ]];
+        assert_eq!(expected, numbers(1, 2));
+    }
+}
@@ -0,0 +1,212 @@
+// Transparent decompression of compressed input.
+//
+// The first bytes of an input are checked for the header of each of the formats in FORMATS.
+// When one of them matches, the input is searched through that format's decoder, so compressed
+// files are searched as if they had been decompressed first, without writing anything to disk.
+// Supporting another format is a matter of adding it to FORMATS.
+use std::io::{self, BufRead, BufReader, Read};
+
+use crate::inflate::Inflate;
+
+type Decode = for<'r> fn(Box<dyn BufRead + 'r>) -> io::Result<Box<dyn BufRead + 'r>>;
+
+pub struct Format {
+    pub name: &'static str,
+    // Whether the data starting with these bytes is in this format
+    starts: fn(&[u8]) -> bool,
+    // None for formats that are recognised but can't be decoded
+    decode: Option<Decode>,
+}
+
+const FORMATS: [Format; 4] = [
+    // The magic bytes and DEFLATE, the only compression method
+    Format { name: "gzip", starts: |bytes| bytes.starts_with(&[0x1f, 0x8b, 0x08]), decode: Some(gzip) },
+    Format { name: "zstd", starts: |bytes| bytes.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]), decode: None },
+    Format { name: "xz", starts: |bytes| bytes.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]), decode: None },
+    Format { name: "bzip2", starts: is_bzip2, decode: None },
+];
+
+// Returns the format of the data starting with `bytes`, if it is compressed
+pub fn detect(bytes: &[u8]) -> Option<&'static Format> {
+    FORMATS.iter().find(|format| (format.starts)(bytes))
+}
+
+// "BZh" is plain text, so the block size digit and the magic of the first block, or of the end
+// of an empty stream, have to follow
+fn is_bzip2(bytes: &[u8]) -> bool {
+    match bytes {
+        [b'B', b'Z', b'h', b'1'..=b'9', block @ ..] => block.starts_with(b"1AY&SY") || block.starts_with(&[0x17, 0x72, 0x45, 0x38, 0x50, 0x90]),
+        _ => false,
+    }
+}
+
+// Returns a reader of the decompressed data, or the reader itself if it isn't compressed.
+// Formats that can't be decoded are an Unsupported error, corrupt data an InvalidData error.
+pub fn decoder<'r, R: BufRead + 'r>(mut reader: R) -> io::Result<Box<dyn BufRead + 'r>> {
+    let format = match detect(reader.fill_buf()?) {
+        Some(format) => format,
+        None => return Ok(Box::new(reader)),
+    };
+    match format.decode {
+        Some(decode) => decode(Box::new(reader)),
+        None => Err(io::Error::new(io::ErrorKind::Unsupported, format!("{} compressed input is not supported", format.name))),
+    }
+}
+
+fn gzip<'r>(reader: Box<dyn BufRead + 'r>) -> io::Result<Box<dyn BufRead + 'r>> {
+    Ok(Box::new(BufReader::new(GzDecoder { member: Some(Member::new(reader)?) })))
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("invalid gzip data: {}", message))
+}
+
+// Reads gzip data (RFC 1952). A gzip file is one or more members, each a header, DEFLATE data
+// and a trailer with the CRC-32 and size of the uncompressed data.
+struct GzDecoder<R: BufRead> {
+    // None once every member was read
+    member: Option<Member<R>>,
+}
+
+struct Member<R: BufRead> {
+    inflate: Inflate<R>,
+    crc: u32,
+    size: u32,
+}
+
+impl<R: BufRead> Member<R> {
+    // Reads the member's header
+    fn new(mut reader: R) -> io::Result<Member<R>> {
+        let mut header = [0; 10];
+        reader.read_exact(&mut header)?;
+        if header[..2] != [0x1f, 0x8b] {
+            return Err(invalid("wrong magic bytes"));
+        }
+        if header[2] != 8 {
+            return Err(invalid("unknown compression method"));
+        }
+
+        let flags = header[3];
+        // Extra field
+        if flags & 0x04 != 0 {
+            let mut length = [0; 2];
+            reader.read_exact(&mut length)?;
+            io::copy(&mut (&mut reader).take(u64::from(u16::from_le_bytes(length))), &mut io::sink())?;
+        }
+        // File name and comment, both ending with a NUL byte
+        for flag in [0x08, 0x10] {
+            if flags & flag != 0 {
+                reader.read_until(0, &mut Vec::new())?;
+            }
+        }
+        // CRC-16 of the header
+        if flags & 0x02 != 0 {
+            reader.read_exact(&mut [0; 2])?;
+        }
+
+        Ok(Member { inflate: Inflate::new(reader), crc: 0, size: 0 })
+    }
+
+    // Checks the trailer, and returns the reader to read the next member from
+    fn finish(self) -> io::Result<R> {
+        let mut reader = self.inflate.into_inner();
+        let mut trailer = [0; 8];
+        reader.read_exact(&mut trailer)?;
+        if u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]) != self.crc {
+            return Err(invalid("CRC mismatch"));
+        }
+        if u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]) != self.size {
+            return Err(invalid("size mismatch"));
+        }
+        Ok(reader)
+    }
+}
+
+impl<R: BufRead> Read for GzDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while let Some(member) = &mut self.member {
+            let n = member.inflate.read(buf)?;
+            if n > 0 {
+                member.crc = crc32(member.crc, &buf[..n]);
+                member.size = member.size.wrapping_add(n as u32);
+                return Ok(n);
+            }
+
+            // Concatenated gzip files are a valid gzip file too
+            let mut reader = self.member.take().unwrap().finish()?;
+            if !reader.fill_buf()?.is_empty() {
+                self.member = Some(Member::new(reader)?);
+            }
+        }
+        Ok(0)
+    }
+}
+
+// The CRC-32 used by gzip, computed a bit at a time
+fn crc32(crc: u32, bytes: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for &byte in bytes {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    // "Then there's a pair of us!\n"
+    const PAIR: [u8; 47] = [
+        0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0x0b, 0xc9, 0x48, 0xcd, 0x53, 0x28, 0xc9, 0x48,
+        0x2d, 0x4a, 0x55, 0x2f, 0x56, 0x48, 0x54, 0x28, 0x48, 0xcc, 0x2c, 0x52, 0xc8, 0x4f, 0x53, 0x28, 0x2d, 0x56,
+        0xe4, 0x02, 0x00, 0x51, 0x34, 0x00, 0xe7, 0x1b, 0x00, 0x00, 0x00,
+    ];
+
+    fn decode(bytes: &[u8]) -> io::Result<String> {
+        let mut text = String::new();
+        decoder(bytes)?.read_to_string(&mut text)?;
+        Ok(text)
+    }
+
+    #[test]
+    fn plain_text_is_passed_through() {
+        assert_eq!("Rust:\n", decode(b"Rust:\n").unwrap());
+    }
+
+    #[test]
+    fn gzip_members_are_decoded() {
+        assert_eq!("Then there's a pair of us!\n", decode(&PAIR).unwrap());
+        assert_eq!("Then there's a pair of us!\n".repeat(2), decode(&[PAIR, PAIR].concat()).unwrap());
+        assert_eq!(fs::read_to_string("poem.txt").unwrap(), decode(&fs::read("poem.txt.gz").unwrap()).unwrap());
+    }
+
+    #[test]
+    fn corrupt_gzip_is_an_error() {
+        let mut corrupt = PAIR;
+        corrupt[40] ^= 1;
+        assert_eq!(io::ErrorKind::InvalidData, decode(&corrupt).unwrap_err().kind());
+    }
+
+    #[test]
+    fn unsupported_formats_are_an_error() {
+        let error = decode(&[0x28, 0xb5, 0x2f, 0xfd, 0x00]).err().unwrap();
+        assert_eq!(io::ErrorKind::Unsupported, error.kind());
+        assert_eq!("zstd compressed input is not supported", error.to_string());
+    }
+
+    #[test]
+    fn text_like_a_magic_is_not_compressed() {
+        assert_eq!("BZh is a prefix\nfoo\n", decode(b"BZh is a prefix\nfoo\n").unwrap());
+        assert_eq!("BZh9 is a size\n", decode(b"BZh9 is a size\n").unwrap());
+        assert_eq!("bzip2", detect(b"BZh91AY&SY\x00").unwrap().name);
+    }
+
+    #[test]
+    fn crc32_of_known_input() {
+        assert_eq!(0xcbf4_3926, crc32(0, b"123456789"));
+    }
+}
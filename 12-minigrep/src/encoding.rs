@@ -0,0 +1,196 @@
+// Text encodings of the input. Everything is searched as UTF-8, so other encodings are decoded first.
+//
+// Without --encoding, a byte order mark (BOM) at the start of the input tells UTF-16 apart, as in
+// files written by many Windows programs, and anything else is taken to be UTF-8. Invalid UTF-8 is
+// not an error: the invalid bytes are replaced with U+FFFD when the line is searched (see stream).
+use std::io::{self, BufRead, BufReader, Read};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    // ISO-8859-1, every byte is the character with that code point
+    Latin1,
+}
+
+const UTF8_BOM: &[u8] = &[0xef, 0xbb, 0xbf];
+const UTF16LE_BOM: &[u8] = &[0xff, 0xfe];
+const UTF16BE_BOM: &[u8] = &[0xfe, 0xff];
+
+impl Encoding {
+    fn bom(self) -> &'static [u8] {
+        match self {
+            Encoding::Utf8 => UTF8_BOM,
+            Encoding::Utf16Le => UTF16LE_BOM,
+            Encoding::Utf16Be => UTF16BE_BOM,
+            Encoding::Latin1 => &[],
+        }
+    }
+}
+
+impl FromStr for Encoding {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Encoding, ()> {
+        match value.to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" => Ok(Encoding::Utf8),
+            "utf-16le" | "utf16le" => Ok(Encoding::Utf16Le),
+            "utf-16be" | "utf16be" => Ok(Encoding::Utf16Be),
+            "latin1" | "latin-1" | "iso-8859-1" => Ok(Encoding::Latin1),
+            _ => Err(()),
+        }
+    }
+}
+
+// Returns the encoding of the data starting with `bytes`, and the length of its byte order mark.
+// `encoding` is the one that was asked for, if any.
+pub fn detect(bytes: &[u8], encoding: Option<Encoding>) -> (Encoding, usize) {
+    let encoding = encoding.unwrap_or_else(|| {
+        [Encoding::Utf8, Encoding::Utf16Le, Encoding::Utf16Be]
+            .into_iter()
+            .find(|encoding| bytes.starts_with(encoding.bom()))
+            .unwrap_or(Encoding::Utf8)
+    });
+    let bom = if bytes.starts_with(encoding.bom()) { encoding.bom().len() } else { 0 };
+    (encoding, bom)
+}
+
+// Returns a reader of the input decoded to UTF-8, without the byte order mark
+pub fn decoder<'r, R: BufRead + 'r>(mut reader: R, encoding: Option<Encoding>) -> io::Result<Box<dyn BufRead + 'r>> {
+    let (encoding, bom) = detect(reader.fill_buf()?, encoding);
+    reader.consume(bom);
+    Ok(match encoding {
+        Encoding::Utf8 => Box::new(reader),
+        Encoding::Utf16Le => Box::new(BufReader::new(Decoder::new(reader, utf16(u16::from_le_bytes)))),
+        Encoding::Utf16Be => Box::new(BufReader::new(Decoder::new(reader, utf16(u16::from_be_bytes)))),
+        Encoding::Latin1 => Box::new(BufReader::new(Decoder::new(reader, latin1))),
+    })
+}
+
+fn latin1(bytes: &[u8], _end: bool, output: &mut String) -> usize {
+    output.extend(bytes.iter().map(|&byte| char::from(byte)));
+    bytes.len()
+}
+
+// UTF-16 with the given byte order. Unpaired surrogates and a final odd byte become U+FFFD.
+fn utf16(unit: fn([u8; 2]) -> u16) -> impl Fn(&[u8], bool, &mut String) -> usize {
+    move |bytes, end, output| {
+        let units: Vec<u16> = bytes.chunks_exact(2).map(|pair| unit([pair[0], pair[1]])).collect();
+        // A high surrogate at the end may still get its low surrogate from the next bytes
+        let whole = match units.last() {
+            Some(0xd800..=0xdbff) if !end => units.len() - 1,
+            _ => units.len(),
+        };
+        output.extend(char::decode_utf16(units[..whole].iter().copied()).map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER)));
+
+        let used = whole * 2;
+        if end && used < bytes.len() {
+            output.push(char::REPLACEMENT_CHARACTER);
+            return bytes.len();
+        }
+        used
+    }
+}
+
+struct Decoder<R: BufRead, D> {
+    inner: R,
+    // Decodes as many whole characters as the bytes start with into the string, and returns how many
+    // bytes that took. The flag is set when there are no more bytes, so the last ones have to be used up.
+    decode: D,
+    // Decoded text that wasn't read yet, from `read` on
+    decoded: String,
+    read: usize,
+    // Bytes too few to decode on their own, waiting for the next ones
+    pending: Vec<u8>,
+}
+
+impl<R: BufRead, D: Fn(&[u8], bool, &mut String) -> usize> Decoder<R, D> {
+    fn new(inner: R, decode: D) -> Decoder<R, D> {
+        Decoder { inner, decode, decoded: String::new(), read: 0, pending: Vec::new() }
+    }
+}
+
+impl<R: BufRead, D: Fn(&[u8], bool, &mut String) -> usize> Read for Decoder<R, D> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.read == self.decoded.len() {
+            self.decoded.clear();
+            self.read = 0;
+
+            let bytes = self.inner.fill_buf()?;
+            let end = bytes.is_empty();
+            if end && self.pending.is_empty() {
+                return Ok(0);
+            }
+            let length = bytes.len();
+            self.pending.extend_from_slice(bytes);
+            self.inner.consume(length);
+
+            let used = (self.decode)(&self.pending, end, &mut self.decoded);
+            self.pending.drain(..used);
+        }
+
+        let n = buf.len().min(self.decoded.len() - self.read);
+        buf[..n].copy_from_slice(&self.decoded.as_bytes()[self.read..self.read + n]);
+        self.read += n;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(bytes: &[u8], encoding: Option<Encoding>) -> String {
+        let mut text = String::new();
+        decoder(bytes, encoding).unwrap().read_to_string(&mut text).unwrap();
+        text
+    }
+
+    fn utf16le(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(|unit| unit.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn byte_order_marks_are_detected() {
+        assert_eq!((Encoding::Utf8, 0), detect(b"Rust:", None));
+        assert_eq!((Encoding::Utf8, 3), detect(b"\xef\xbb\xbfRust:", None));
+        assert_eq!((Encoding::Utf16Le, 2), detect(b"\xff\xfeR\0", None));
+        assert_eq!((Encoding::Utf16Be, 2), detect(b"\xfe\xff\0R", None));
+        assert_eq!((Encoding::Latin1, 0), detect(b"\xff\xfeR\0", Some(Encoding::Latin1)));
+    }
+
+    #[test]
+    fn utf16_is_decoded() {
+        let text = "Straße 🦀\r\nnobody\n";
+        assert_eq!(text, decode(&[UTF16LE_BOM, &utf16le(text)].concat(), None));
+        let big_endian: Vec<u8> = text.encode_utf16().flat_map(|unit| unit.to_be_bytes()).collect();
+        assert_eq!(text, decode(&[UTF16BE_BOM, &big_endian].concat(), None));
+        // Without a byte order mark, only when asked for
+        assert_eq!(text, decode(&utf16le(text), Some(Encoding::Utf16Le)));
+    }
+
+    #[test]
+    fn utf16_split_across_reads() {
+        let bytes = [UTF16LE_BOM, &utf16le("🦀 crab")].concat();
+        let reader = BufReader::with_capacity(3, &bytes[..]);
+        let mut text = String::new();
+        decoder(reader, None).unwrap().read_to_string(&mut text).unwrap();
+        assert_eq!("🦀 crab", text);
+    }
+
+    #[test]
+    fn invalid_utf16_is_replaced() {
+        // A lone low surrogate, and an odd byte at the end
+        assert_eq!("a\u{fffd}b\u{fffd}", decode(&[b'a', 0, 0x00, 0xdc, b'b', 0, b'c'], Some(Encoding::Utf16Le)));
+    }
+
+    #[test]
+    fn latin1_and_utf8() {
+        assert_eq!("café", decode(b"caf\xe9", Some(Encoding::Latin1)));
+        assert_eq!("café", decode("\u{feff}café".as_bytes(), None));
+        assert_eq!(Ok(Encoding::Utf16Le), "UTF-16LE".parse());
+        assert!("ebcdic".parse::<Encoding>().is_err());
+    }
+}
@@ -0,0 +1,110 @@
+// Everything that can make a search fail, and the exit code it leads to.
+//
+// The exit codes are the ones grep uses: 0 when a line was selected, 1 when none was and 2 for an
+// error. A file that can't be read doesn't stop the search: its error is reported when it happens,
+// the other files are still searched, and the search ends with Skipped so the exit code is 2.
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::args::ArgsError;
+use crate::regex;
+
+#[derive(Debug)]
+pub enum MinigrepError {
+    // The command line is wrong
+    Usage(ArgsError),
+    Regex(regex::Error),
+    // A file couldn't be read, or the output couldn't be written when there is no path
+    Io { path: Option<PathBuf>, error: io::Error },
+    // A file is corrupt, e.g. compressed data that can't be decompressed
+    Encoding { path: PathBuf, message: String },
+    // This many files couldn't be searched, their errors were already reported
+    Skipped(usize),
+    // The search went fine but didn't select any line
+    NoMatch,
+}
+
+impl MinigrepError {
+    // The error of reading a file
+    pub fn file(path: &Path, error: io::Error) -> MinigrepError {
+        if error.kind() == io::ErrorKind::InvalidData {
+            return MinigrepError::Encoding { path: path.to_path_buf(), message: error.to_string() };
+        }
+        MinigrepError::Io { path: Some(path.to_path_buf()), error }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            MinigrepError::NoMatch => 1,
+            _ => 2,
+        }
+    }
+}
+
+impl fmt::Display for MinigrepError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MinigrepError::Usage(error) => write!(f, "{}", error),
+            MinigrepError::Regex(error) => write!(f, "{}", error),
+            MinigrepError::Io { path: Some(path), error } => write!(f, "{}: {}", path.display(), error),
+            MinigrepError::Io { path: None, error } => write!(f, "{}", error),
+            MinigrepError::Encoding { path, message } => write!(f, "{}: {}", path.display(), message),
+            MinigrepError::Skipped(1) => write!(f, "1 file couldn't be searched"),
+            MinigrepError::Skipped(count) => write!(f, "{} files couldn't be searched", count),
+            MinigrepError::NoMatch => write!(f, "no match"),
+        }
+    }
+}
+
+impl Error for MinigrepError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MinigrepError::Usage(error) => Some(error),
+            MinigrepError::Regex(error) => Some(error),
+            MinigrepError::Io { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<ArgsError> for MinigrepError {
+    fn from(error: ArgsError) -> MinigrepError {
+        MinigrepError::Usage(error)
+    }
+}
+
+impl From<regex::Error> for MinigrepError {
+    fn from(error: regex::Error) -> MinigrepError {
+        MinigrepError::Regex(error)
+    }
+}
+
+// Errors without a path, such as writing the output
+impl From<io::Error> for MinigrepError {
+    fn from(error: io::Error) -> MinigrepError {
+        MinigrepError::Io { path: None, error }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exit_codes_are_like_grep() {
+        assert_eq!(1, MinigrepError::NoMatch.exit_code());
+        assert_eq!(2, MinigrepError::Usage(ArgsError::MissingQuery).exit_code());
+        assert_eq!(2, MinigrepError::Skipped(3).exit_code());
+    }
+
+    #[test]
+    fn file_errors_name_the_file() {
+        let error = MinigrepError::file(Path::new("poem.txt"), io::Error::new(io::ErrorKind::NotFound, "not found"));
+        assert_eq!("poem.txt: not found", error.to_string());
+        let error = MinigrepError::file(Path::new("poem.gz"), io::Error::new(io::ErrorKind::InvalidData, "corrupt gzip data"));
+        assert!(matches!(error, MinigrepError::Encoding { .. }));
+        assert_eq!("1 file couldn't be searched", MinigrepError::Skipped(1).to_string());
+    }
+}
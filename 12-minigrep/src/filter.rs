@@ -0,0 +1,178 @@
+// Which files found in directories are searched: --type, --type-not and --glob.
+//
+// A file type is a name for a few globs, e.g. `rust` is `*.rs`, from the table below. With --type
+// only files of the given types are searched, and --type-not leaves out files of the given types.
+//
+// --glob works like a line of an ignore file turned around: files matching `*.toml` are searched
+// and nothing else is, while `!target/**` leaves out what matches. As in ignore files, a glob with a
+// '/' is matched against the path below the searched directory, otherwise against the file name,
+// and the last matching glob wins. Directories that a `!` glob leaves out are not entered at all.
+//
+// Files given on the command line are always searched, the filters only apply to directories.
+use crate::args::ArgsError;
+use crate::glob::Glob;
+
+// File type names and the file names they stand for, sorted by name
+pub const FILE_TYPES: [(&str, &[&str]); 25] = [
+    ("c", &["*.c", "*.h"]),
+    ("cpp", &["*.cpp", "*.cc", "*.cxx", "*.hpp", "*.hh", "*.hxx"]),
+    ("css", &["*.css"]),
+    ("go", &["*.go"]),
+    ("html", &["*.html", "*.htm"]),
+    ("java", &["*.java"]),
+    ("js", &["*.js", "*.mjs", "*.cjs", "*.jsx"]),
+    ("json", &["*.json"]),
+    ("lock", &["*.lock"]),
+    ("make", &["Makefile", "makefile", "GNUmakefile", "*.mk"]),
+    ("markdown", &["*.md", "*.markdown"]),
+    ("md", &["*.md", "*.markdown"]),
+    ("py", &["*.py", "*.pyi"]),
+    ("python", &["*.py", "*.pyi"]),
+    ("rb", &["*.rb", "Gemfile", "Rakefile"]),
+    ("ruby", &["*.rb", "Gemfile", "Rakefile"]),
+    ("rust", &["*.rs"]),
+    ("sh", &["*.sh", "*.bash", "*.zsh"]),
+    ("sql", &["*.sql"]),
+    ("toml", &["*.toml"]),
+    ("ts", &["*.ts", "*.tsx"]),
+    ("txt", &["*.txt"]),
+    ("xml", &["*.xml"]),
+    ("yaml", &["*.yaml", "*.yml"]),
+    ("yml", &["*.yaml", "*.yml"]),
+];
+
+#[derive(Debug, Clone)]
+struct PathGlob {
+    glob: Glob,
+    negated: bool,
+    // Matched against the relative path instead of the file name
+    anchored: bool,
+}
+
+impl PathGlob {
+    fn matches(&self, path: &str, name: &str) -> bool {
+        self.glob.is_match(if self.anchored { path } else { name })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    globs: Vec<PathGlob>,
+    // When some globs are not negated, only the files they match are searched
+    whitelist: bool,
+    types: Vec<Glob>,
+    types_not: Vec<Glob>,
+}
+
+impl Filter {
+    pub fn new(types: &[String], types_not: &[String], globs: &[String]) -> Result<Filter, ArgsError> {
+        let mut filter = Filter { types: type_globs(types)?, types_not: type_globs(types_not)?, ..Filter::default() };
+
+        for value in globs {
+            let (negated, pattern) = match value.strip_prefix('!') {
+                Some(pattern) => (true, pattern),
+                None => (false, value.as_str()),
+            };
+            let anchored = pattern.contains('/');
+            let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+            let glob = Glob::new(pattern)
+                .map_err(|_| ArgsError::InvalidValue { option: String::from("glob"), value: value.clone() })?;
+            filter.whitelist |= !negated;
+            filter.globs.push(PathGlob { glob, negated, anchored });
+        }
+
+        Ok(filter)
+    }
+
+    // `path` is relative to the directory being searched, with '/' as the separator
+    pub fn is_file_searched(&self, path: &str) -> bool {
+        let name = path.rsplit('/').next().unwrap_or(path);
+        let by_glob = match self.globs.iter().rev().find(|glob| glob.matches(path, name)) {
+            Some(glob) => !glob.negated,
+            None => !self.whitelist,
+        };
+        by_glob
+            && (self.types.is_empty() || self.types.iter().any(|glob| glob.is_match(name)))
+            && !self.types_not.iter().any(|glob| glob.is_match(name))
+    }
+
+    pub fn is_dir_searched(&self, path: &str) -> bool {
+        let name = path.rsplit('/').next().unwrap_or(path);
+        // With the trailing '/', `target/**` matches the directory itself too
+        let dir = format!("{}/", path);
+        match self.globs.iter().rev().find(|glob| glob.matches(path, name) || glob.matches(&dir, name)) {
+            Some(glob) => !glob.negated,
+            None => true,
+        }
+    }
+}
+
+fn type_globs(names: &[String]) -> Result<Vec<Glob>, ArgsError> {
+    let mut globs = Vec::new();
+    for name in names {
+        let (_, patterns) = FILE_TYPES.iter()
+            .find(|(type_name, _)| type_name == name)
+            .ok_or_else(|| ArgsError::UnknownFileType(name.clone()))?;
+        // The table only has valid globs
+        globs.extend(patterns.iter().map(|pattern| Glob::new(pattern).unwrap()));
+    }
+    Ok(globs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(types: &[&str], types_not: &[&str], globs: &[&str]) -> Filter {
+        let strings = |values: &[&str]| values.iter().map(|value| String::from(*value)).collect::<Vec<_>>();
+        Filter::new(&strings(types), &strings(types_not), &strings(globs)).unwrap()
+    }
+
+    #[test]
+    fn file_types_select_files() {
+        let rust = filter(&["rust", "toml"], &[], &[]);
+        assert!(rust.is_file_searched("src/main.rs"));
+        assert!(rust.is_file_searched("Cargo.toml"));
+        assert!(!rust.is_file_searched("README.md"));
+
+        let not_markdown = filter(&[], &["md"], &[]);
+        assert!(!not_markdown.is_file_searched("docs/README.md"));
+        assert!(not_markdown.is_file_searched("src/main.rs"));
+    }
+
+    #[test]
+    fn globs_include_and_exclude() {
+        let toml = filter(&[], &[], &["*.toml"]);
+        assert!(toml.is_file_searched("Cargo.toml"));
+        assert!(toml.is_file_searched("crates/a/Cargo.toml"));
+        assert!(!toml.is_file_searched("src/main.rs"));
+        assert!(toml.is_dir_searched("crates"));
+
+        let no_target = filter(&[], &[], &["!target/**", "!*.lock"]);
+        assert!(!no_target.is_dir_searched("target"));
+        assert!(no_target.is_dir_searched("src/target"));
+        assert!(!no_target.is_file_searched("Cargo.lock"));
+        assert!(no_target.is_file_searched("src/main.rs"));
+    }
+
+    #[test]
+    fn last_matching_glob_wins() {
+        let filter = filter(&[], &[], &["*.rs", "!src/bin/*.rs"]);
+        assert!(filter.is_file_searched("src/lib.rs"));
+        assert!(!filter.is_file_searched("src/bin/main.rs"));
+    }
+
+    #[test]
+    fn unknown_types_and_invalid_globs_are_errors() {
+        let result = Filter::new(&[String::from("cobol")], &[], &[]);
+        assert_eq!(Some(ArgsError::UnknownFileType(String::from("cobol"))), result.err());
+        let result = Filter::new(&[], &[], &[String::from("src/[z-a].rs")]);
+        assert!(matches!(result, Err(ArgsError::InvalidValue { .. })));
+    }
+
+    #[test]
+    fn file_types_are_sorted_and_valid() {
+        assert!(FILE_TYPES.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert!(FILE_TYPES.iter().flat_map(|(_, globs)| globs.iter()).all(|glob| Glob::new(glob).is_ok()));
+    }
+}
@@ -0,0 +1,225 @@
+// Fast byte and substring search over a whole buffer.
+//
+// memchr looks at eight bytes at a time: XOR-ing a word with the searched byte repeated eight times
+// turns matching bytes into zero bytes, and a classic bit trick tells whether a word has a zero byte.
+//
+// Finder uses the Two-Way algorithm (Crochemore and Perrin), which is what str::find uses as well:
+// the needle is split at a "critical position", the right part is compared left to right and then
+// the left part right to left. Thanks to the needle's period, a mismatch can skip ahead without ever
+// going back in the haystack, so a search takes linear time with constant extra memory.
+// Candidate positions are found first with memchr, looking for the needle's rarest byte, which
+// skips most of the haystack quickly.
+const LOW_BITS: u64 = 0x0101_0101_0101_0101;
+const HIGH_BITS: u64 = 0x8080_8080_8080_8080;
+
+fn has_zero_byte(word: u64) -> bool {
+    word.wrapping_sub(LOW_BITS) & !word & HIGH_BITS != 0
+}
+
+// Returns the index of the first `needle` byte in `haystack`
+pub fn memchr(needle: u8, haystack: &[u8]) -> Option<usize> {
+    let repeated = LOW_BITS * u64::from(needle);
+    let mut chunks = haystack.chunks_exact(8);
+
+    for (index, chunk) in chunks.by_ref().enumerate() {
+        let word = u64::from_le_bytes(chunk.try_into().unwrap());
+        if has_zero_byte(word ^ repeated) {
+            return chunk.iter().position(|b| *b == needle).map(|offset| index * 8 + offset);
+        }
+    }
+
+    let offset = haystack.len() - chunks.remainder().len();
+    chunks.remainder().iter().position(|b| *b == needle).map(|index| offset + index)
+}
+
+// Returns the index of the last `needle` byte in `haystack`
+pub fn memrchr(needle: u8, haystack: &[u8]) -> Option<usize> {
+    let repeated = LOW_BITS * u64::from(needle);
+    let mut chunks = haystack.rchunks_exact(8);
+
+    for (index, chunk) in chunks.by_ref().enumerate() {
+        let word = u64::from_le_bytes(chunk.try_into().unwrap());
+        if has_zero_byte(word ^ repeated) {
+            let start = haystack.len() - (index + 1) * 8;
+            return chunk.iter().rposition(|b| *b == needle).map(|offset| start + offset);
+        }
+    }
+
+    chunks.remainder().iter().rposition(|b| *b == needle)
+}
+
+#[derive(Debug, Clone)]
+pub struct Finder {
+    needle: Vec<u8>,
+    // The needle is split into needle[..critical] and needle[critical..]
+    critical: usize,
+    period: usize,
+    // Index of the byte that is least likely to occur in text, searched for with memchr
+    rare: usize,
+    // When the needle isn't periodic, the period is only a safe shift and nothing is remembered
+    // about the previous position after a mismatch in the left part
+    long_period: bool,
+}
+
+impl Finder {
+    pub fn new(needle: &[u8]) -> Finder {
+        let (critical_less, period_less) = maximal_suffix(needle, false);
+        let (critical_greater, period_greater) = maximal_suffix(needle, true);
+        let (critical, period) = if critical_less > critical_greater {
+            (critical_less, period_less)
+        } else {
+            (critical_greater, period_greater)
+        };
+
+        let rare = (0..needle.len()).min_by_key(|&index| frequency(needle[index])).unwrap_or(0);
+
+        if period <= needle.len() && needle[..critical] == needle[period..period + critical] {
+            Finder { needle: needle.to_vec(), critical, period, rare, long_period: false }
+        } else {
+            let period = critical.max(needle.len() - critical) + 1;
+            Finder { needle: needle.to_vec(), critical, period, rare, long_period: true }
+        }
+    }
+
+    pub fn needle(&self) -> &[u8] {
+        &self.needle
+    }
+
+    // Returns the index of the first occurrence of the needle in `haystack`
+    pub fn find(&self, haystack: &[u8]) -> Option<usize> {
+        let needle = &self.needle;
+        if needle.is_empty() {
+            return Some(0);
+        }
+
+        let mut position = 0;
+        // How much of the start of the needle is known to match at `position`
+        let mut memory = 0;
+
+        'search: while position + needle.len() <= haystack.len() {
+            if memory == 0 {
+                // Skip to the next place where the rare byte matches
+                let skip = memchr(needle[self.rare], &haystack[position + self.rare..])?;
+                position += skip;
+                if position + needle.len() > haystack.len() {
+                    return None;
+                }
+            }
+
+            // The right part, left to right
+            let start = self.critical.max(memory);
+            for i in start..needle.len() {
+                if needle[i] != haystack[position + i] {
+                    position += i - self.critical + 1;
+                    memory = 0;
+                    continue 'search;
+                }
+            }
+
+            // The left part, right to left
+            for i in (memory..self.critical).rev() {
+                if needle[i] != haystack[position + i] {
+                    position += self.period;
+                    if !self.long_period {
+                        memory = needle.len() - self.period;
+                    }
+                    continue 'search;
+                }
+            }
+
+            return Some(position);
+        }
+
+        None
+    }
+}
+
+// A rough rank of how common a byte is in text, higher is more common
+fn frequency(byte: u8) -> u8 {
+    match byte {
+        b' ' => 255,
+        b'e' => 250,
+        b't' | b'a' | b'o' => 245,
+        b'i' | b'n' | b's' | b'r' | b'h' => 240,
+        b'l' | b'd' | b'c' | b'u' | b'\n' => 230,
+        b'm' | b'w' | b'f' | b'g' | b'y' | b'p' | b',' | b'.' => 220,
+        b'b' | b'v' | b'k' | b'0'..=b'9' | b'_' | b'\t' => 200,
+        b'a'..=b'z' | b'A'..=b'Z' => 180,
+        // Bytes of non-ASCII characters
+        0x80..=0xff => 120,
+        _ => 150,
+    }
+}
+
+// Returns the start of the lexicographically largest suffix of `needle` and the period of that suffix.
+// `reversed` compares bytes in the reverse order, which gives the other candidate critical position.
+fn maximal_suffix(needle: &[u8], reversed: bool) -> (usize, usize) {
+    let mut left = 0;
+    let mut right = 1;
+    let mut offset = 0;
+    let mut period = 1;
+
+    while let Some(&a) = needle.get(right + offset) {
+        let b = needle[left + offset];
+        if (a < b && !reversed) || (a > b && reversed) {
+            // The suffix at `right` is smaller, so it extends the period
+            right += offset + 1;
+            offset = 0;
+            period = right - left;
+        } else if a == b {
+            if offset + 1 == period {
+                right += offset + 1;
+                offset = 0;
+            } else {
+                offset += 1;
+            }
+        } else {
+            // The suffix at `right` is larger and becomes the candidate
+            left = right;
+            right += 1;
+            offset = 0;
+            period = 1;
+        }
+    }
+
+    (left, period)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(needle: &str, haystack: &str) -> Option<usize> {
+        Finder::new(needle.as_bytes()).find(haystack.as_bytes())
+    }
+
+    #[test]
+    fn memchr_finds_first_and_last() {
+        let haystack = b"Rust:\nsafe, fast, productive.\nPick three.";
+        assert_eq!(Some(5), memchr(b'\n', haystack));
+        assert_eq!(Some(29), memrchr(b'\n', haystack));
+        assert_eq!(Some(40), memchr(b'.', &haystack[30..]).map(|index| index + 30));
+        assert_eq!(None, memchr(b'x', haystack));
+        assert_eq!(None, memrchr(b'x', haystack));
+        assert_eq!(None, memchr(b'x', b""));
+    }
+
+    #[test]
+    fn finder_matches_str_find() {
+        let haystacks = ["", "a", "aaaaaaaaab", "abababababc", "banana bandana", "Rust:\nsafe, fast, productive.", "xyzxyzxyzz"];
+        let needles = ["", "a", "ab", "aab", "abc", "ana", "andana", "fast", "productive.", "xyzz", "zxyzz", "nope"];
+        for haystack in haystacks {
+            for needle in needles {
+                assert_eq!(haystack.find(needle), find(needle, haystack), "{:?} in {:?}", needle, haystack);
+            }
+        }
+    }
+
+    #[test]
+    fn finder_handles_periodic_needles() {
+        let haystack = "ab".repeat(100) + "abc";
+        assert_eq!(Some(198), find("ababc", &haystack));
+        assert_eq!(Some(0), find(&"ab".repeat(50), &haystack));
+        assert_eq!(None, find("aaaa", &haystack));
+    }
+}
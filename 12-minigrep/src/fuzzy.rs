@@ -0,0 +1,141 @@
+// Approximate matching for --fuzzy: finds the parts of a line within a given edit distance of the query.
+//
+// The edit (Levenshtein) distance is the number of characters that have to be inserted, deleted or
+// substituted to turn one string into the other. A match can start anywhere in the line, so this is
+// Sellers' variant of the usual dynamic programming table, where starting at any position is free.
+// The table is filled one column per character of the line, and only the last column is kept,
+// together with where the match ending in each cell started.
+use crate::casefold::simple_fold;
+
+#[derive(Debug, Clone)]
+pub struct Fuzzy {
+    query: Vec<char>,
+    max_distance: usize,
+    case_insensitive: bool,
+}
+
+// Byte range of a match in the line, and its edit distance to the query
+pub type FuzzyMatch = (usize, usize, usize);
+
+impl Fuzzy {
+    pub fn new(query: &str, max_distance: usize, case_insensitive: bool) -> Fuzzy {
+        let query = query.chars().map(|c| if case_insensitive { simple_fold(c) } else { c }).collect();
+        Fuzzy { query, max_distance, case_insensitive }
+    }
+
+    // Returns the first match at or after `start`. Once a match is found, it is extended as long as
+    // that doesn't take it further from the query, so "nobody" finds all of "nbody" and not just "nbod".
+    pub fn find_at(&self, text: &str, start: usize) -> Option<FuzzyMatch> {
+        let mut found: Option<FuzzyMatch> = None;
+        self.scan(text, start, |candidate| match found {
+            None if candidate.2 <= self.max_distance => {
+                found = Some(candidate);
+                true
+            }
+            None => true,
+            Some(best) if is_better(candidate, best) => {
+                found = Some(candidate);
+                true
+            }
+            Some(_) => false,
+        });
+        found
+    }
+
+    // Returns the match closest to the query, the first one if there are several
+    pub fn best_match(&self, text: &str) -> Option<FuzzyMatch> {
+        let mut best: Option<FuzzyMatch> = None;
+        self.scan(text, 0, |candidate| {
+            if candidate.2 <= self.max_distance && best.is_none_or(|best| is_better(candidate, best)) {
+                best = Some(candidate);
+            }
+            // Nothing beats an exact match
+            best.is_none_or(|(_, _, distance)| distance > 0)
+        });
+        best
+    }
+
+    // Calls `found` with the best match ending at every character boundary from `start` on,
+    // until it returns false
+    fn scan<F>(&self, text: &str, start: usize, mut found: F)
+    where
+        F: FnMut(FuzzyMatch) -> bool,
+    {
+        // distances[i] is the distance between the first i characters of the query and the best
+        // part of the text ending at the current position, which starts at begins[i]
+        let mut distances: Vec<usize> = (0..=self.query.len()).collect();
+        let mut begins = vec![start; self.query.len() + 1];
+        let last = self.query.len();
+
+        if !found((begins[last], start, distances[last])) {
+            return;
+        }
+
+        for (offset, c) in text[start..].char_indices() {
+            let end = start + offset + c.len_utf8();
+            let c = if self.case_insensitive { simple_fold(c) } else { c };
+
+            // The cell diagonally up-left, from the previous column
+            let mut diagonal = (distances[0], begins[0]);
+            // Matching no query characters costs nothing, wherever it starts
+            begins[0] = end;
+
+            for i in 1..=last {
+                let substitute = (diagonal.0 + usize::from(self.query[i - 1] != c), diagonal.1);
+                // An extra character in the text
+                let insert = (distances[i] + 1, begins[i]);
+                // A query character missing from the text
+                let delete = (distances[i - 1] + 1, begins[i - 1]);
+
+                diagonal = (distances[i], begins[i]);
+                // On a tie, the match that starts first is the longest
+                let best = [substitute, insert, delete].into_iter().min().unwrap();
+                distances[i] = best.0;
+                begins[i] = best.1;
+            }
+
+            if !found((begins[last], end, distances[last])) {
+                return;
+            }
+        }
+    }
+}
+
+// A closer match is better, and so is a longer match that is just as close
+fn is_better(candidate: FuzzyMatch, best: FuzzyMatch) -> bool {
+    candidate.2 < best.2 || (candidate.2 == best.2 && candidate.0 == best.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exact_and_close_matches() {
+        let fuzzy = Fuzzy::new("nobody", 1, false);
+        assert_eq!(Some((4, 10, 0)), fuzzy.find_at("I'm nobody!", 0));
+        assert_eq!(Some((4, 9, 1)), fuzzy.find_at("I'm nbody!", 0));
+        assert_eq!(Some((4, 11, 1)), fuzzy.find_at("I'm noobody!", 0));
+        assert_eq!(Some((4, 10, 1)), fuzzy.find_at("I'm nobady!", 0));
+        assert_eq!(None, fuzzy.find_at("I'm nbdy!", 0));
+    }
+
+    #[test]
+    fn best_match_prefers_the_closest() {
+        let fuzzy = Fuzzy::new("frog", 2, false);
+        assert_eq!(Some((13, 17, 0)), fuzzy.best_match("fog or from, frog"));
+        assert_eq!(Some((0, 3, 1)), fuzzy.best_match("fog or from"));
+        assert_eq!(None, Fuzzy::new("frog", 0, false).best_match("fog or from"));
+    }
+
+    #[test]
+    fn ignoring_case() {
+        assert_eq!(Some((0, 6, 1)), Fuzzy::new("NOBODY", 1, true).best_match("Nobodi"));
+        assert_eq!(None, Fuzzy::new("NOBODY", 1, false).best_match("Nobodi"));
+    }
+
+    #[test]
+    fn offsets_are_in_bytes() {
+        assert_eq!(Some((12, 20, 2)), Fuzzy::new("strassen", 2, true).best_match("Die großen Straßen"));
+    }
+}
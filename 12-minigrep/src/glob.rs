@@ -0,0 +1,132 @@
+// Shell style glob patterns, as used by .gitignore files.
+//
+// A glob is translated into an anchored regular expression and matched with our own regex engine:
+// - `*` matches anything except a '/'
+// - `?` matches a single character except a '/'
+// - `[abc]`, `[a-z]` and `[!a-z]` match a single character from (or not from) a set
+// - `**` matches anything including '/', so `**/foo`, `foo/**` and `a/**/b` work across directories
+// - `\` escapes the next character
+use crate::regex::{self, Regex};
+
+#[derive(Debug, Clone)]
+pub struct Glob {
+    regex: Regex,
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> Result<Glob, regex::Error> {
+        let regex = Regex::new(&to_regex(pattern))?;
+        Ok(Glob { regex })
+    }
+
+    // The whole path has to match, paths use '/' as a separator
+    pub fn is_match(&self, path: &str) -> bool {
+        self.regex.is_match(path)
+    }
+}
+
+fn to_regex(pattern: &str) -> String {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut regex = String::from("^");
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                let at_segment_start = i == 0 || chars[i - 1] == '/';
+                if at_segment_start && chars.get(i + 2) == Some(&'/') {
+                    // `**/` matches zero or more directories
+                    regex.push_str("(?:.*/)?");
+                    i += 3;
+                } else {
+                    regex.push_str(".*");
+                    i += 2;
+                }
+                continue;
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            '[' => match chars[i + 1..].iter().skip(1).position(|c| *c == ']') {
+                Some(offset) => {
+                    // The first character after '[' (or '[!') can be a literal ']'
+                    let end = i + 2 + offset;
+                    regex.push('[');
+                    let mut start = i + 1;
+                    if chars[start] == '!' || chars[start] == '^' {
+                        regex.push('^');
+                        start += 1;
+                    }
+                    for c in &chars[start..end] {
+                        if *c == '\\' || *c == '[' {
+                            regex.push('\\');
+                        }
+                        regex.push(*c);
+                    }
+                    regex.push(']');
+                    i = end;
+                }
+                None => regex.push_str("\\["),
+            },
+            '\\' if i + 1 < chars.len() => {
+                i += 1;
+                push_literal(&mut regex, chars[i]);
+            }
+            c => push_literal(&mut regex, c),
+        }
+        i += 1;
+    }
+
+    regex.push('$');
+    regex
+}
+
+fn push_literal(regex: &mut String, c: char) {
+    if !c.is_alphanumeric() && !c.is_whitespace() {
+        regex.push('\\');
+    }
+    regex.push(c);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_match(pattern: &str, path: &str) -> bool {
+        Glob::new(pattern).unwrap().is_match(path)
+    }
+
+    #[test]
+    fn star_does_not_cross_directories() {
+        assert!(is_match("*.rs", "main.rs"));
+        assert!(!is_match("*.rs", "src/main.rs"));
+        assert!(is_match("src/*.rs", "src/main.rs"));
+        assert!(!is_match("*.rs", "main.rsx"));
+    }
+
+    #[test]
+    fn double_star_crosses_directories() {
+        assert!(is_match("**/*.rs", "main.rs"));
+        assert!(is_match("**/*.rs", "src/bin/main.rs"));
+        assert!(is_match("target/**", "target/debug/minigrep"));
+        assert!(is_match("a/**/b", "a/b"));
+        assert!(is_match("a/**/b", "a/x/y/b"));
+        assert!(!is_match("a/**/b", "a/x/y/c"));
+    }
+
+    #[test]
+    fn question_mark_and_classes() {
+        assert!(is_match("file?.txt", "file1.txt"));
+        assert!(!is_match("file?.txt", "file10.txt"));
+        assert!(is_match("file[0-9].txt", "file7.txt"));
+        assert!(!is_match("file[!0-9].txt", "file7.txt"));
+        assert!(is_match("file[]].txt", "file].txt"));
+    }
+
+    #[test]
+    fn special_characters_are_literal() {
+        assert!(is_match("a+b(1).txt", "a+b(1).txt"));
+        assert!(!is_match("a.txt", "abtxt"));
+        assert!(is_match("\\*.txt", "*.txt"));
+        assert!(!is_match("\\*.txt", "a.txt"));
+    }
+}
@@ -0,0 +1,382 @@
+// A trigram index of a directory, built with `minigrep index build DIR`, so that searching it again
+// and again only reads the files that can contain the query.
+//
+// For every file below the directory, the index stores the set of trigrams (runs of three bytes) of
+// its text, decoded and case folded (see casefold), so case-insensitive queries can use it too.
+// A file can only contain the query if it has every trigram of the query, so the other files are
+// not searched. The index is kept in DIR/.minigrep-index
+// with the modification time and size of every file. Building it again only reads the files that
+// changed, and a file that changed since the index was built is always searched.
+//
+// Only plain queries of three bytes or more use the index: regexes, --fuzzy and --invert-match can
+// match text without the query's trigrams.
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::binary;
+use crate::casefold;
+use crate::decompress;
+use crate::encoding;
+use crate::filter::Filter;
+use crate::walk;
+use crate::Config;
+
+pub const INDEX_FILE: &str = ".minigrep-index";
+// Where the index is written before it replaces the old one
+const INDEX_TEMP_FILE: &str = ".minigrep-index.tmp";
+
+// The start of the index file, the last byte is the version of the format
+const MAGIC: &[u8] = b"minigrep-index\x01";
+
+// A trigram, packed into the low three bytes
+type Trigram = u32;
+
+#[derive(Debug, Clone, PartialEq)]
+struct Entry {
+    modified: SystemTime,
+    size: u64,
+    // Sorted
+    trigrams: Vec<Trigram>,
+}
+
+impl Entry {
+    // Whether the file is still the one that was indexed
+    fn is_current(&self, metadata: &fs::Metadata) -> bool {
+        metadata.modified().is_ok_and(|modified| modified == self.modified) && metadata.len() == self.size
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Index {
+    // By path relative to the indexed directory
+    entries: HashMap<PathBuf, Entry>,
+}
+
+// The numbers reported after building an index
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Built {
+    pub files: usize,
+    // Files that were read, because they are new or changed
+    pub updated: usize,
+}
+
+impl Index {
+    // Returns None when the directory has no index
+    pub fn open(dir: &Path) -> io::Result<Option<Index>> {
+        match fs::read(dir.join(INDEX_FILE)) {
+            Ok(bytes) => read_index(&mut &bytes[..]).map(Some),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    // Builds the index of the directory, or updates the one it has
+    pub fn build(dir: &Path) -> io::Result<Built> {
+        // An index that can't be read, e.g. from another version, is built again from scratch
+        let mut old = Index::open(dir).unwrap_or_default().unwrap_or_default();
+        let mut index = Index::default();
+        let mut built = Built::default();
+        let mut set = TrigramSet::new();
+
+        // Files that can't be read are left out like binary files, searching reports them
+        for path in walk::walk(dir, &Filter::default(), &mut Vec::new()) {
+            let relative = match path.strip_prefix(dir) {
+                Ok(relative) => relative.to_path_buf(),
+                Err(_) => continue,
+            };
+            let metadata = match fs::metadata(&path) {
+                Ok(metadata) => metadata,
+                Err(_) => continue,
+            };
+            let entry = match old.entries.remove(&relative) {
+                Some(entry) if entry.is_current(&metadata) => entry,
+                _ => match index_file(&path, &metadata, &mut set) {
+                    Ok(Some(entry)) => {
+                        built.updated += 1;
+                        entry
+                    }
+                    // Binary files and compressed files we can't decode are left out too, so
+                    // searching them goes on as without an index
+                    Ok(None) | Err(_) => continue,
+                },
+            };
+            index.entries.insert(relative, entry);
+        }
+        built.files = index.entries.len();
+
+        // Written next to the old index and renamed over it, so a search never sees half an index
+        let temp = dir.join(INDEX_TEMP_FILE);
+        let result = File::create(&temp)
+            .and_then(|file| {
+                let mut out = BufWriter::new(file);
+                write_index(&index, &mut out)?;
+                out.into_inner().map_err(|e| e.into_error())?.sync_all()
+            })
+            .and_then(|_| fs::rename(&temp, dir.join(INDEX_FILE)));
+        if result.is_err() {
+            let _ = fs::remove_file(&temp);
+        }
+        result.map(|_| built)
+    }
+
+    // Whether the file at `path`, found below `dir`, has to be searched for the query
+    pub fn may_match(&self, dir: &Path, path: &Path, query: &Query) -> bool {
+        let entry = match path.strip_prefix(dir).ok().and_then(|relative| self.entries.get(relative)) {
+            Some(entry) => entry,
+            None => return true,
+        };
+        if !fs::metadata(path).is_ok_and(|metadata| entry.is_current(&metadata)) {
+            return true;
+        }
+        query.patterns.iter().any(|trigrams| trigrams.iter().all(|trigram| entry.trigrams.binary_search(trigram).is_ok()))
+    }
+}
+
+// Whether a file with this name is an index or one being written, which are never searched
+pub fn is_index_file(name: &OsStr) -> bool {
+    name == INDEX_FILE || name == INDEX_TEMP_FILE
+}
+
+// The trigrams a file needs to match any of the patterns
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    patterns: Vec<Vec<Trigram>>,
+}
+
+impl Query {
+    // Returns None when the index can't tell which files may match
+    pub fn new(config: &Config) -> Option<Query> {
+        if config.regex || config.fuzzy.is_some() || config.invert_match || config.encoding.is_some() {
+            return None;
+        }
+        let mut patterns = Vec::new();
+        for pattern in &config.patterns {
+            let folded = folded(pattern);
+            if folded.len() < 3 {
+                return None;
+            }
+            patterns.push(trigrams(folded.as_bytes()));
+        }
+        // No patterns match nothing, but that doesn't need an index
+        if patterns.is_empty() {
+            return None;
+        }
+        Some(Query { patterns })
+    }
+}
+
+// Folding every character on its own keeps a match's text together: when the query is in the text,
+// the folded query is in the folded text, whether it is matched case-insensitively or not
+fn folded(text: &str) -> String {
+    let mut folded = String::with_capacity(text.len());
+    for c in text.chars() {
+        folded.extend(casefold::fold(c).as_slice());
+    }
+    folded
+}
+
+fn trigram(bytes: &[u8]) -> Trigram {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0])
+}
+
+// The sorted, distinct trigrams of the text
+fn trigrams(text: &[u8]) -> Vec<Trigram> {
+    let mut trigrams: Vec<Trigram> = text.windows(3).map(trigram).collect();
+    trigrams.sort_unstable();
+    trigrams.dedup();
+    trigrams
+}
+
+// Finds the distinct trigrams of whole files without sorting every trigram of a big file.
+// There is a bit for every possible trigram, which takes 2 MiB, so one set is used for every file.
+struct TrigramSet {
+    seen: Vec<u64>,
+}
+
+impl TrigramSet {
+    fn new() -> TrigramSet {
+        TrigramSet { seen: vec![0; (1 << 24) / 64] }
+    }
+
+    // Like trigrams
+    fn trigrams(&mut self, text: &[u8]) -> Vec<Trigram> {
+        let mut trigrams = Vec::new();
+        for window in text.windows(3) {
+            let trigram = trigram(window);
+            let (word, bit) = ((trigram / 64) as usize, 1 << (trigram % 64));
+            if self.seen[word] & bit == 0 {
+                self.seen[word] |= bit;
+                trigrams.push(trigram);
+            }
+        }
+        // Every bit that is set belongs to one of the trigrams
+        for trigram in &trigrams {
+            self.seen[(trigram / 64) as usize] = 0;
+        }
+        trigrams.sort_unstable();
+        trigrams
+    }
+}
+
+// Reads the file as it is searched: decompressed and decoded, with invalid UTF-8 replaced, then folded
+// Returns None for binary files
+fn index_file(path: &Path, metadata: &fs::Metadata, set: &mut TrigramSet) -> io::Result<Option<Entry>> {
+    let reader = decompress::decoder(BufReader::new(File::open(path)?))?;
+    let mut bytes = Vec::new();
+    encoding::decoder(reader, None)?.read_to_end(&mut bytes)?;
+    if binary::is_binary(&bytes) {
+        return Ok(None);
+    }
+    let text = folded(&String::from_utf8_lossy(&bytes));
+    Ok(Some(Entry { modified: metadata.modified()?, size: metadata.len(), trigrams: set.trigrams(text.as_bytes()) }))
+}
+
+// The index file is the magic bytes, the number of files, and for each file its path, modification
+// time, size and trigrams. Numbers are little-endian, and a trigram takes three bytes.
+fn write_index<W: Write>(index: &Index, out: &mut W) -> io::Result<()> {
+    out.write_all(MAGIC)?;
+    // Paths that are not valid UTF-8 are left out, so those files are always searched
+    let entries: Vec<(&str, &Entry)> = index.entries.iter()
+        .filter_map(|(path, entry)| Some((path.to_str()?, entry)))
+        .collect();
+    out.write_all(&(entries.len() as u64).to_le_bytes())?;
+
+    for (path, entry) in entries {
+        let modified = entry.modified.duration_since(UNIX_EPOCH).unwrap_or_default();
+        out.write_all(&(path.len() as u64).to_le_bytes())?;
+        out.write_all(path.as_bytes())?;
+        out.write_all(&modified.as_secs().to_le_bytes())?;
+        out.write_all(&modified.subsec_nanos().to_le_bytes())?;
+        out.write_all(&entry.size.to_le_bytes())?;
+        out.write_all(&(entry.trigrams.len() as u64).to_le_bytes())?;
+        for trigram in &entry.trigrams {
+            out.write_all(&trigram.to_le_bytes()[..3])?;
+        }
+    }
+    Ok(())
+}
+
+// Every length is checked against what is left of the input, so a corrupt index is an error
+// instead of a huge allocation
+fn read_index(input: &mut &[u8]) -> io::Result<Index> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "not a minigrep index, or from another version");
+    if read_bytes(input, MAGIC.len() as u64)? != MAGIC {
+        return Err(invalid());
+    }
+
+    let mut index = Index::default();
+    for _ in 0..read_u64(input)? {
+        let length = read_u64(input)?;
+        let path = String::from_utf8(read_bytes(input, length)?.to_vec()).map_err(|_| invalid())?;
+        let secs = read_u64(input)?;
+        let nanos = u32::from_le_bytes(read_bytes(input, 4)?.try_into().unwrap());
+        if nanos >= 1_000_000_000 {
+            return Err(invalid());
+        }
+        let modified = UNIX_EPOCH.checked_add(Duration::new(secs, nanos)).ok_or_else(invalid)?;
+        let size = read_u64(input)?;
+
+        let length = read_u64(input)?.checked_mul(3).ok_or_else(invalid)?;
+        let trigrams = read_bytes(input, length)?.chunks_exact(3).map(trigram).collect();
+        index.entries.insert(PathBuf::from(path), Entry { modified, size, trigrams });
+    }
+    Ok(index)
+}
+
+// Takes the next `length` bytes off the input
+fn read_bytes<'a>(input: &mut &'a [u8], length: u64) -> io::Result<&'a [u8]> {
+    let length = usize::try_from(length).ok().filter(|length| *length <= input.len())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "the index is truncated or corrupt"))?;
+    let (bytes, rest) = input.split_at(length);
+    *input = rest;
+    Ok(bytes)
+}
+
+fn read_u64(input: &mut &[u8]) -> io::Result<u64> {
+    Ok(u64::from_le_bytes(read_bytes(input, 8)?.try_into().unwrap()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn query(patterns: &[&str], case_sensitive: bool) -> Option<Query> {
+        let patterns = patterns.iter().map(|pattern| String::from(*pattern)).collect();
+        Query::new(&Config { patterns, case_sensitive, ..Config::default() })
+    }
+
+    #[test]
+    fn trigram_sets_are_sorted_and_distinct() {
+        let mut set = TrigramSet::new();
+        assert_eq!(trigrams(b"abcabcd"), set.trigrams(b"abcabcd"));
+        assert_eq!(4, set.trigrams(b"abcabcd").len());
+        assert_eq!(vec![trigram(b"xyz")], set.trigrams(b"xyz"));
+        assert!(set.trigrams(b"ab").is_empty());
+    }
+
+    #[test]
+    fn only_plain_queries_use_the_index() {
+        assert!(query(&["nobody"], true).is_some());
+        assert!(query(&["no"], true).is_none());
+        assert!(query(&[], true).is_none());
+        assert!(query(&["nobody", "us"], true).is_none());
+        let config = Config { patterns: vec![String::from("nobody")], regex: true, ..Config::default() };
+        assert!(Query::new(&config).is_none());
+    }
+
+    #[test]
+    fn build_prunes_and_updates() {
+        let dir = env::temp_dir().join(format!("minigrep-index-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("poem.txt"), "I'm nobody! Who are you?\n").unwrap();
+        fs::write(dir.join("german.txt"), "Die STRASSE ist lang\n").unwrap();
+        fs::write(dir.join("binary"), b"nobody\0").unwrap();
+
+        assert_eq!(None, Index::open(&dir).unwrap());
+        assert_eq!(Built { files: 2, updated: 2 }, Index::build(&dir).unwrap());
+        let index = Index::open(&dir).unwrap().unwrap();
+        let may_match = |name: &str, query: &Option<Query>| index.may_match(&dir, &dir.join(name), query.as_ref().unwrap());
+        assert!(may_match("poem.txt", &query(&["nobody"], true)));
+        assert!(!may_match("german.txt", &query(&["nobody"], true)));
+        // Folded on both sides, so case-insensitive queries find their matches too
+        assert!(may_match("german.txt", &query(&["straße"], false)));
+        assert!(may_match("german.txt", &query(&["frog", "Strasse"], false)));
+        // Files the index doesn't know are searched, and binary files are left out of it
+        assert!(may_match("new.txt", &query(&["nobody"], true)));
+        assert!(may_match("binary", &query(&["frog"], true)));
+
+        // A changed file is searched until the index is updated
+        fs::write(dir.join("german.txt"), "Ich bin nobody, und du?\n").unwrap();
+        assert!(may_match("german.txt", &query(&["nobody"], true)));
+        assert_eq!(Built { files: 2, updated: 1 }, Index::build(&dir).unwrap());
+        assert_eq!(Built { files: 2, updated: 0 }, Index::build(&dir).unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn index_files_round_trip() {
+        let mut index = Index::default();
+        let modified = UNIX_EPOCH + Duration::new(1_700_000_000, 123);
+        index.entries.insert(PathBuf::from("src/main.rs"), Entry { modified, size: 42, trigrams: trigrams(b"fn main") });
+        let mut bytes = Vec::new();
+        write_index(&index, &mut bytes).unwrap();
+        assert_eq!(index, read_index(&mut &bytes[..]).unwrap());
+
+        // Lengths that go past the end of the file, or overflow, are errors
+        let trigram_count = bytes.len() - trigrams(b"fn main").len() * 3 - 8;
+        for count in [u64::MAX, u64::MAX / 3 + 1, 1 << 40] {
+            let mut corrupt = bytes.clone();
+            corrupt[trigram_count..trigram_count + 8].copy_from_slice(&count.to_le_bytes());
+            assert_eq!(io::ErrorKind::InvalidData, read_index(&mut &corrupt[..]).unwrap_err().kind());
+        }
+        assert_eq!(io::ErrorKind::InvalidData, read_index(&mut &bytes[..bytes.len() - 1]).unwrap_err().kind());
+
+        bytes[0] = b'M';
+        assert_eq!(io::ErrorKind::InvalidData, read_index(&mut &bytes[..]).unwrap_err().kind());
+    }
+}
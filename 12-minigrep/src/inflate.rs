@@ -0,0 +1,297 @@
+// A DEFLATE decoder (RFC 1951), the compression used by gzip.
+//
+// The compressed data is a sequence of blocks. A block is either stored as is, or compressed with
+// Huffman codes (fixed ones, or ones described at the start of the block) into literal bytes and
+// <length, distance> pairs that copy earlier output. Copies reach back at most 32 KiB, so that much
+// output is kept around. Blocks are decoded one at a time, as the output is read.
+use std::io::{self, BufRead, Read};
+
+const WINDOW_SIZE: usize = 32 * 1024;
+const MAX_BITS: usize = 15;
+
+// Base values and extra bits of the length codes 257 to 285, and of the distance codes 0 to 29
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097,
+    6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+
+// The order in which the code lengths of the code length alphabet are stored
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("invalid compressed data: {}", message))
+}
+
+// Reads the input a bit at a time, least significant bit first.
+// Whole bytes are only taken from the reader when needed, so nothing is read past the end of the data.
+struct BitReader<R: BufRead> {
+    inner: R,
+    bits: u32,
+    count: u32,
+}
+
+impl<R: BufRead> BitReader<R> {
+    fn bits(&mut self, n: u32) -> io::Result<u32> {
+        while self.count < n {
+            let byte = match self.inner.fill_buf()?.first() {
+                Some(byte) => *byte,
+                None => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "compressed data ends too early")),
+            };
+            self.inner.consume(1);
+            self.bits |= u32::from(byte) << self.count;
+            self.count += 8;
+        }
+        let value = self.bits & ((1 << n) - 1);
+        self.bits >>= n;
+        self.count -= n;
+        Ok(value)
+    }
+
+    // Skips to the start of the next byte
+    fn align(&mut self) {
+        self.bits = 0;
+        self.count = 0;
+    }
+}
+
+// A canonical Huffman code, described by the number of codes of each length
+// and the symbols ordered by code
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    // `lengths` holds the code length of every symbol, 0 for symbols that are not used
+    fn new(lengths: &[u8]) -> io::Result<Huffman> {
+        let mut counts = [0; MAX_BITS + 1];
+        for &length in lengths {
+            counts[usize::from(length)] += 1;
+        }
+        counts[0] = 0;
+
+        // Make sure there aren't more codes of a length than there is room for
+        let mut left: i32 = 1;
+        for &count in &counts[1..] {
+            left = left * 2 - i32::from(count);
+            if left < 0 {
+                return Err(invalid("too many Huffman codes"));
+            }
+        }
+
+        let mut offsets = [0; MAX_BITS + 1];
+        for length in 1..MAX_BITS {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[usize::from(offsets[usize::from(length)])] = symbol as u16;
+                offsets[usize::from(length)] += 1;
+            }
+        }
+
+        Ok(Huffman { counts, symbols })
+    }
+
+    // Reads one code bit by bit. Codes of each length are consecutive numbers,
+    // so the code is complete once it falls in the range of its length.
+    fn decode<R: BufRead>(&self, bits: &mut BitReader<R>) -> io::Result<u16> {
+        let mut code = 0;
+        let mut first = 0;
+        let mut index = 0;
+        for &count in &self.counts[1..] {
+            code |= bits.bits(1)? as i32;
+            let count = i32::from(count);
+            if code - count < first {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(invalid("unknown Huffman code"))
+    }
+}
+
+pub struct Inflate<R: BufRead> {
+    bits: BitReader<R>,
+    // The last WINDOW_SIZE bytes that were read, followed by the output that wasn't read yet
+    window: Vec<u8>,
+    // Where the unread output starts in the window
+    read: usize,
+    last_block: bool,
+}
+
+impl<R: BufRead> Inflate<R> {
+    pub fn new(inner: R) -> Inflate<R> {
+        Inflate { bits: BitReader { inner, bits: 0, count: 0 }, window: Vec::new(), read: 0, last_block: false }
+    }
+
+    // Returns the reader, positioned at the first byte after the compressed data.
+    // Only valid once everything has been read.
+    pub fn into_inner(self) -> R {
+        self.bits.inner
+    }
+
+    fn block(&mut self) -> io::Result<()> {
+        self.last_block = self.bits.bits(1)? == 1;
+        match self.bits.bits(2)? {
+            0 => self.stored(),
+            1 => {
+                let mut lengths = [0; 288];
+                lengths[..144].fill(8);
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                lengths[280..].fill(8);
+                self.compressed(&Huffman::new(&lengths)?, &Huffman::new(&[5; 30])?)
+            }
+            2 => {
+                let (literals, distances) = self.dynamic_codes()?;
+                self.compressed(&literals, &distances)
+            }
+            _ => Err(invalid("unknown block type")),
+        }
+    }
+
+    fn stored(&mut self) -> io::Result<()> {
+        self.bits.align();
+        let length = self.bits.bits(16)?;
+        if length != !self.bits.bits(16)? & 0xffff {
+            return Err(invalid("stored block length doesn't match its complement"));
+        }
+        let start = self.window.len();
+        self.window.resize(start + length as usize, 0);
+        self.bits.inner.read_exact(&mut self.window[start..])
+    }
+
+    fn dynamic_codes(&mut self) -> io::Result<(Huffman, Huffman)> {
+        let literal_count = self.bits.bits(5)? as usize + 257;
+        let distance_count = self.bits.bits(5)? as usize + 1;
+        let code_length_count = self.bits.bits(4)? as usize + 4;
+
+        let mut code_lengths = [0; 19];
+        for &symbol in &CODE_LENGTH_ORDER[..code_length_count] {
+            code_lengths[symbol] = self.bits.bits(3)? as u8;
+        }
+        let code_lengths = Huffman::new(&code_lengths)?;
+
+        // The literal/length and distance code lengths are stored as one run-length encoded sequence
+        let mut lengths = Vec::with_capacity(literal_count + distance_count);
+        while lengths.len() < literal_count + distance_count {
+            let (length, repeat) = match code_lengths.decode(&mut self.bits)? {
+                symbol @ 0..=15 => (symbol as u8, 1),
+                16 => match lengths.last() {
+                    Some(&previous) => (previous, 3 + self.bits.bits(2)?),
+                    None => return Err(invalid("repeated code length without a previous one")),
+                },
+                17 => (0, 3 + self.bits.bits(3)?),
+                _ => (0, 11 + self.bits.bits(7)?),
+            };
+            lengths.extend(std::iter::repeat_n(length, repeat as usize));
+        }
+        if lengths.len() > literal_count + distance_count {
+            return Err(invalid("too many code lengths"));
+        }
+        if lengths[256] == 0 {
+            return Err(invalid("no code for the end of the block"));
+        }
+
+        Ok((Huffman::new(&lengths[..literal_count])?, Huffman::new(&lengths[literal_count..])?))
+    }
+
+    fn compressed(&mut self, literals: &Huffman, distances: &Huffman) -> io::Result<()> {
+        loop {
+            let symbol = usize::from(literals.decode(&mut self.bits)?);
+            if symbol < 256 {
+                self.window.push(symbol as u8);
+                continue;
+            }
+            if symbol == 256 {
+                return Ok(());
+            }
+
+            let code = symbol - 257;
+            if code >= LENGTH_BASE.len() {
+                return Err(invalid("unknown length code"));
+            }
+            let length = usize::from(LENGTH_BASE[code]) + self.bits.bits(u32::from(LENGTH_EXTRA[code]))? as usize;
+
+            let code = usize::from(distances.decode(&mut self.bits)?);
+            if code >= DISTANCE_BASE.len() {
+                return Err(invalid("unknown distance code"));
+            }
+            let distance = usize::from(DISTANCE_BASE[code]) + self.bits.bits(u32::from(DISTANCE_EXTRA[code]))? as usize;
+            if distance > self.window.len() {
+                return Err(invalid("distance too far back"));
+            }
+
+            // The copy can overlap the bytes it produces, so copy one byte at a time
+            let start = self.window.len() - distance;
+            for i in 0..length {
+                let byte = self.window[start + i];
+                self.window.push(byte);
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Read for Inflate<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.read == self.window.len() {
+            if self.last_block {
+                return Ok(0);
+            }
+            // Everything was read, only keep what the next block can refer to
+            if self.window.len() > WINDOW_SIZE {
+                self.window.drain(..self.window.len() - WINDOW_SIZE);
+            }
+            self.read = self.window.len();
+            self.block()?;
+        }
+
+        let n = buf.len().min(self.window.len() - self.read);
+        buf[..n].copy_from_slice(&self.window[self.read..self.read + n]);
+        self.read += n;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inflate(data: &[u8]) -> io::Result<Vec<u8>> {
+        let mut output = Vec::new();
+        Inflate::new(data).read_to_end(&mut output)?;
+        Ok(output)
+    }
+
+    #[test]
+    fn stored_block() {
+        assert_eq!(b"hello".to_vec(), inflate(&[0x01, 0x05, 0x00, 0xfa, 0xff, b'h', b'e', b'l', b'l', b'o']).unwrap());
+        let error = inflate(&[0x01, 0x05, 0x00, 0x00, 0x00, b'h']).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, error.kind());
+    }
+
+    #[test]
+    fn fixed_huffman_block() {
+        // "I'm nobody! Who are you? Are you nobody, too?"
+        let data = [
+            0xf3, 0x54, 0xcf, 0x55, 0xc8, 0xcb, 0x4f, 0xca, 0x4f, 0xa9, 0x54, 0x54, 0x08, 0xcf, 0xc8, 0x57, 0x48, 0x2c,
+            0x4a, 0x55, 0xa8, 0xcc, 0x2f, 0xb5, 0x57, 0x70, 0x84, 0x30, 0xa0, 0x92, 0x3a, 0x0a, 0x25, 0xf9, 0xf9, 0xf6,
+            0x00,
+        ];
+        assert_eq!(&b"I'm nobody! Who are you? Are you nobody, too?"[..], &inflate(&data).unwrap()[..]);
+    }
+
+    #[test]
+    fn truncated_data_is_an_error() {
+        assert_eq!(io::ErrorKind::UnexpectedEof, inflate(&[0xf3, 0x54]).unwrap_err().kind());
+    }
+}
@@ -0,0 +1,177 @@
+// JSON Lines output for --json: one JSON object per line, for tools that consume search results.
+//
+// Each input produces a "begin" record, a "match" record for every matching line
+// (a "context" record for context lines) and an "end" record with the input's stats. A binary input
+// with a match has a "binary_match" record instead of its lines (see binary).
+// A final "summary" record holds the totals for the whole search:
+//
+// {"type":"begin","data":{"path":"poem.txt"}}
+// {"type":"match","data":{"path":"poem.txt","line_number":1,"absolute_offset":0,"text":"I'm nobody! Who are you?","submatches":[{"match":"nobody","start":4,"end":10}]}}
+// {"type":"end","data":{"path":"poem.txt","stats":{"matched_lines":1,"bytes_searched":25}}}
+// {"type":"summary","data":{"elapsed_ms":0,"stats":{"searches":1,"searches_with_match":1,"matched_lines":1,"bytes_searched":25}}}
+//
+// Offsets are in bytes: absolute_offset is where the line starts in the input,
+// start and end are relative to the start of the line. With --fuzzy, match records also have
+// the "distance" between the query and the closest part of the line.
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::context::ContextLine;
+use crate::matcher::Matcher;
+use crate::sink::{Sink, Stats};
+
+pub struct JsonPrinter<'m, W: Write> {
+    out: W,
+    matcher: &'m Matcher,
+}
+
+impl<'m, W: Write> JsonPrinter<'m, W> {
+    pub fn new(matcher: &'m Matcher, out: W) -> JsonPrinter<'m, W> {
+        JsonPrinter { out, matcher }
+    }
+}
+
+impl<W: Write> Sink for JsonPrinter<'_, W> {
+    fn begin(&mut self, path: &Path) -> io::Result<()> {
+        writeln!(self.out, r#"{{"type":"begin","data":{{"path":{}}}}}"#, path_string(path))
+    }
+
+    fn line(&mut self, path: &Path, line: &ContextLine) -> io::Result<()> {
+        let kind = if line.is_match { "match" } else { "context" };
+        write!(
+            self.out,
+            r#"{{"type":"{}","data":{{"path":{},"line_number":{},"absolute_offset":{},"text":{}"#,
+            kind,
+            path_string(path),
+            line.number,
+            line.offset,
+            string(line.text),
+        )?;
+
+        if line.is_match {
+            let submatches: Vec<String> = self.matcher.find_iter(line.text).iter()
+                .map(|(start, end)| format!(r#"{{"match":{},"start":{},"end":{}}}"#, string(&line.text[*start..*end]), start, end))
+                .collect();
+            write!(self.out, r#","submatches":[{}]"#, submatches.join(","))?;
+            if let Some(distance) = self.matcher.distance(line.text) {
+                write!(self.out, r#","distance":{}"#, distance)?;
+            }
+        }
+
+        writeln!(self.out, "}}}}")
+    }
+
+    fn binary_match(&mut self, path: &Path) -> io::Result<()> {
+        writeln!(self.out, r#"{{"type":"binary_match","data":{{"path":{}}}}}"#, path_string(path))
+    }
+
+    fn end(&mut self, path: &Path, stats: &Stats) -> io::Result<()> {
+        writeln!(
+            self.out,
+            r#"{{"type":"end","data":{{"path":{},"stats":{{"matched_lines":{},"bytes_searched":{}}}}}}}"#,
+            path_string(path),
+            stats.matched_lines,
+            stats.bytes_searched,
+        )
+    }
+}
+
+pub fn write_summary<W: Write>(out: &mut W, stats: &Stats, elapsed: Duration) -> io::Result<()> {
+    writeln!(
+        out,
+        r#"{{"type":"summary","data":{{"elapsed_ms":{},"stats":{{"searches":{},"searches_with_match":{},"matched_lines":{},"bytes_searched":{}}}}}}}"#,
+        elapsed.as_millis(),
+        stats.searches,
+        stats.searches_with_match,
+        stats.matched_lines,
+        stats.bytes_searched,
+    )
+}
+
+fn path_string(path: &Path) -> String {
+    string(&path.to_string_lossy())
+}
+
+// Encodes the text as a JSON string, including the quotes
+pub fn string(text: &str) -> String {
+    let mut json = String::with_capacity(text.len() + 2);
+    json.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+
+    #[test]
+    fn string_escapes_special_characters() {
+        assert_eq!(r#""say \"hi\"\\n""#, string("say \"hi\"\\n"));
+        assert_eq!(r#""a\tb\u0001\n""#, string("a\tb\u{1}\n"));
+        assert_eq!(r#""straße""#, string("straße"));
+    }
+
+    #[test]
+    fn prints_records() {
+        let config = Config { patterns: vec![String::from("nobody")], case_sensitive: true, ..Config::default() };
+        let matcher = Matcher::new(&config).unwrap();
+        let mut out = Vec::new();
+        let mut printer = JsonPrinter::new(&matcher, &mut out);
+        let path = Path::new("poem.txt");
+
+        printer.begin(path).unwrap();
+        printer.line(path, &ContextLine { number: 1, offset: 0, text: "nobody, nobody", is_match: true }).unwrap();
+        printer.line(path, &ContextLine { number: 2, offset: 15, text: "Are you", is_match: false }).unwrap();
+        printer.end(path, &Stats { searches: 1, searches_with_match: 1, matched_lines: 1, bytes_searched: 23 }).unwrap();
+
+        let expected = [
+            r#"{"type":"begin","data":{"path":"poem.txt"}}"#,
+            r#"{"type":"match","data":{"path":"poem.txt","line_number":1,"absolute_offset":0,"text":"nobody, nobody","submatches":[{"match":"nobody","start":0,"end":6},{"match":"nobody","start":8,"end":14}]}}"#,
+            r#"{"type":"context","data":{"path":"poem.txt","line_number":2,"absolute_offset":15,"text":"Are you"}}"#,
+            r#"{"type":"end","data":{"path":"poem.txt","stats":{"matched_lines":1,"bytes_searched":23}}}"#,
+        ];
+        assert_eq!(expected.join("\n") + "\n", String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn prints_fuzzy_distance() {
+        let config = Config { patterns: vec![String::from("nobody")], fuzzy: Some(1), ..Config::default() };
+        let matcher = Matcher::new(&config).unwrap();
+        let mut out = Vec::new();
+        let mut printer = JsonPrinter::new(&matcher, &mut out);
+        printer.line(Path::new("poem.txt"), &ContextLine { number: 1, offset: 0, text: "nobdy", is_match: true }).unwrap();
+
+        let expected = r#"{"type":"match","data":{"path":"poem.txt","line_number":1,"absolute_offset":0,"text":"nobdy","submatches":[{"match":"nobdy","start":0,"end":5}],"distance":1}}"#;
+        assert_eq!(format!("{}\n", expected), String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn prints_binary_matches() {
+        let matcher = Matcher::new(&Config { patterns: vec![String::from("nobody")], ..Config::default() }).unwrap();
+        let mut out = Vec::new();
+        JsonPrinter::new(&matcher, &mut out).binary_match(Path::new("a.out")).unwrap();
+        assert_eq!("{\"type\":\"binary_match\",\"data\":{\"path\":\"a.out\"}}\n", String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn prints_summary() {
+        let mut out = Vec::new();
+        let stats = Stats { searches: 2, searches_with_match: 1, matched_lines: 3, bytes_searched: 100 };
+        write_summary(&mut out, &stats, Duration::from_millis(12)).unwrap();
+        let expected = r#"{"type":"summary","data":{"elapsed_ms":12,"stats":{"searches":2,"searches_with_match":1,"matched_lines":3,"bytes_searched":100}}}"#;
+        assert_eq!(format!("{}\n", expected), String::from_utf8(out).unwrap());
+    }
+}
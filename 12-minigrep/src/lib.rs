@@ -1,65 +1,610 @@
+use std::collections::HashMap;
 use std::fs;
-use std::error::Error;
 use std::env;
+use std::io::{self, BufWriter, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Instant;
 
-#[derive(Debug, PartialEq)]
+pub mod aho_corasick;
+pub mod args;
+pub mod binary;
+pub mod buffer;
+pub mod casefold;
+pub mod color;
+pub mod config_file;
+pub mod context;
+pub mod decompress;
+pub mod encoding;
+pub mod error;
+pub mod filter;
+pub mod finder;
+pub mod fuzzy;
+pub mod glob;
+pub mod index;
+pub mod inflate;
+pub mod json;
+pub mod matcher;
+pub mod mmap;
+pub mod multiline;
+pub mod pool;
+pub mod printer;
+pub mod regex;
+pub mod replace;
+pub mod searcher;
+pub mod sink;
+pub mod stream;
+pub mod tui;
+pub mod walk;
+
+use args::{Arg, ArgParser, ArgsError};
+use binary::BinaryFiles;
+use casefold::FoldedQuery;
+use color::ColorChoice;
+use encoding::Encoding;
+use error::MinigrepError;
+use filter::Filter;
+use finder::Finder;
+use fuzzy::Fuzzy;
+use index::Index;
+use json::JsonPrinter;
+use matcher::Matcher;
+use pool::ThreadPool;
+use printer::Printer;
+use sink::{Sink, Stats};
+use regex::Regex;
+use replace::Replacement;
+use searcher::Searcher;
+
+#[derive(Debug, PartialEq, Default)]
 pub struct Config {
-    pub query: String,
-    pub filename: String,
+    // Lines matching any of the patterns are selected
+    pub patterns: Vec<String>,
+    // Files and directories to search, "-" is stdin
+    pub filenames: Vec<String>,
     pub case_sensitive: bool,
+    // Treat the patterns as regular expressions instead of a plain substring
+    pub regex: bool,
+    pub invert_match: bool,
+    // Prefix lines with their number, None numbers them when searching a directory
+    pub line_number: Option<bool>,
+    // Prefix matching lines with the column of the first match, and lines with their byte offset
+    pub column: bool,
+    pub byte_offset: bool,
+    // Print path:line:column:line for every match, see printer
+    pub vimgrep: bool,
+    pub count: bool,
+    // Print only the matched parts of the lines
+    pub only_matching: bool,
+    // Print only the paths of the files with a match, or of the ones without
+    pub files_with_matches: bool,
+    pub files_without_match: bool,
+    // Stop searching a file after this many matching lines
+    pub max_count: Option<usize>,
+    pub word_regexp: bool,
+    // Match the patterns against the whole input, so matches can span lines
+    pub multiline: bool,
+    // Number of lines to print after and before each match
+    pub after_context: usize,
+    pub before_context: usize,
+    // Number of files searched at the same time, 0 picks one per CPU
+    pub threads: usize,
+    pub color: ColorChoice,
+    // Prefix lines with the path, None decides based on the number of files searched
+    pub with_filename: Option<bool>,
+    // Print JSON Lines instead of text
+    pub json: bool,
+    // Match approximately, within this edit distance of a pattern
+    pub fuzzy: Option<usize>,
+    // Print matching lines with every match replaced by this text
+    pub replace: Option<String>,
+    // Write the replacements back to the files instead of printing them
+    pub in_place: bool,
+    // Keep a copy of each edited file, named with this suffix appended
+    pub backup_suffix: Option<String>,
+    // Read files instead of memory-mapping them
+    pub no_mmap: bool,
+    // The encoding of the input, None detects it
+    pub encoding: Option<Encoding>,
+    // File types and globs selecting the files searched in directories, see filter
+    pub types: Vec<String>,
+    pub types_not: Vec<String>,
+    pub globs: Vec<String>,
+    // Browse the matches in the terminal instead of printing them, see tui
+    pub interactive: bool,
+    // Search every file, even when the directory has an index
+    pub no_index: bool,
+    // What to do with binary inputs
+    pub binary_files: BinaryFiles,
+    // Build the index of this directory instead of searching, from `minigrep index build DIR`
+    pub build_index: Option<String>,
 }
 
 impl Config {
-    pub fn new<T>(mut args: T) -> Result<Config, &'static str>
+    // Options from the file named by MINIGREP_CONFIG are the defaults, see config_file
+    pub fn new<T>(args: T) -> Result<Config, ArgsError>
+    where
+        T: Iterator<Item = String>
+    {
+        let config_file = env::var_os("MINIGREP_CONFIG").filter(|path| !path.is_empty()).map(PathBuf::from);
+        Config::with_config_file(args, config_file.as_deref())
+    }
+
+    pub fn with_config_file<T>(mut args: T, config_file: Option<&Path>) -> Result<Config, ArgsError>
     where
         T: Iterator<Item = String>
     {
         // First value is the name of the program
         args.next();
+        let args: Vec<String> = args.collect();
+        // The index command, without options or config file. Anything else starting with "index"
+        // is a search for it.
+        if let [command, build, dir] = &args[..] {
+            if command == "index" && build == "build" {
+                return Ok(Config { build_index: Some(dir.clone()), ..Config::default() });
+            }
+        }
 
-        let query = match args.next() {
-            Some(arg) => arg,
-            None => return Err("Didn't get a query string")
-        };
+        // If the environment variable CASE_INSENSITIVE is not set, make the search case sensitive.
+        // The -i and -s flags override it.
+        let mut config = Config { case_sensitive: env::var("CASE_INSENSITIVE").is_err(), ..Config::default() };
+        let mut parsed = Parsed::default();
+
+        // The config file comes first, so the command line overrides it. Whether to read it at all
+        // depends on --no-config, so the command line is parsed on its own first.
+        let mut command_line = Parsed::default();
+        parse_options(&mut Config::default(), ArgParser::new(args.iter().cloned()), &mut command_line)?;
+        if let Some(path) = config_file.filter(|_| !command_line.no_config) {
+            for (line, line_args) in config_file::read(path)? {
+                let text = line_args.join(" ");
+                let in_file = |error| ArgsError::InConfigFile {
+                    path: path.display().to_string(),
+                    line,
+                    error: Box::new(error),
+                };
+                parse_options(&mut config, ArgParser::new(line_args.into_iter()), &mut parsed).map_err(in_file)?;
+                if !parsed.positional.is_empty() || parsed.patterns_given {
+                    return Err(in_file(ArgsError::NotInConfigFile(text)));
+                }
+            }
+            parsed.resolve_context(&mut config);
+        }
+        parse_options(&mut config, ArgParser::new(args.into_iter()), &mut parsed)?;
+        parsed.resolve_context(&mut config);
 
-        let filename = match args.next() {
-            Some(arg) => arg,
-            None => return Err("Didn't get a file name")
+        if config.fuzzy.is_some() && config.regex {
+            return Err(ArgsError::ConflictingOptions(String::from("fuzzy"), String::from("regex")));
+        }
+        // Replacements are made line by line
+        if config.in_place && config.multiline {
+            return Err(ArgsError::ConflictingOptions(String::from("multiline"), String::from("in-place")));
+        }
+        if config.in_place && config.interactive {
+            return Err(ArgsError::ConflictingOptions(String::from("interactive"), String::from("in-place")));
+        }
+        if config.in_place && config.replace.is_none() {
+            return Err(ArgsError::RequiresOption { option: String::from("in-place"), required: String::from("replace") });
+        }
+        // Unknown file types and invalid globs are reported before searching anything
+        Filter::new(&config.types, &config.types_not, &config.globs)?;
+
+        let mut positional = parsed.positional.into_iter();
+        if !parsed.patterns_given {
+            config.patterns.push(positional.next().ok_or(ArgsError::MissingQuery)?);
+        }
+        config.filenames = positional.collect();
+        // The query is edited while browsing, and there is only one
+        if config.interactive && config.patterns.len() > 1 {
+            return Err(ArgsError::SinglePattern(String::from("interactive")));
+        }
+        // Without a file name we read from stdin, like grep. The interactive mode needs stdin for
+        // the keyboard and searches again and again, so it searches the current directory instead.
+        if config.filenames.is_empty() {
+            config.filenames.push(String::from(if config.interactive { "." } else { "-" }));
+        }
+
+        Ok(config)
+    }
+}
+
+fn parse_options<T>(config: &mut Config, mut args: ArgParser<T>, parsed: &mut Parsed) -> Result<(), ArgsError>
+where
+    T: Iterator<Item = String>
+{
+    while let Some(arg) = args.next_arg()? {
+        let name = match arg {
+            Arg::Positional(value) => {
+                parsed.positional.push(value);
+                continue;
+            }
+            Arg::Option(name) => name,
         };
 
-        // If the environment variable CASE_INSENSITIVE is not set, make the search case sensitive
-        let case_sensitive = env::var("CASE_INSENSITIVE").is_err();
+        match name.as_str() {
+            "ignore-case" => config.case_sensitive = false,
+            "case-sensitive" => config.case_sensitive = true,
+            "invert-match" => config.invert_match = true,
+            "line-number" => config.line_number = Some(true),
+            "no-line-number" => config.line_number = Some(false),
+            "column" => config.column = true,
+            "byte-offset" => config.byte_offset = true,
+            "vimgrep" => config.vimgrep = true,
+            "count" => config.count = true,
+            "only-matching" => config.only_matching = true,
+            "files-with-matches" => config.files_with_matches = true,
+            "files-without-match" => config.files_without_match = true,
+            "max-count" => config.max_count = Some(args.parsed_value()?),
+            "word-regexp" => config.word_regexp = true,
+            "multiline" => config.multiline = true,
+            "regex" => config.regex = true,
+            "regexp" => {
+                config.patterns.push(args.value()?);
+                parsed.patterns_given = true;
+            }
+            "file" => {
+                config.patterns.extend(read_patterns(&args.value()?)?);
+                parsed.patterns_given = true;
+            }
+            "after-context" => parsed.after_context = Some(args.parsed_value()?),
+            "before-context" => parsed.before_context = Some(args.parsed_value()?),
+            "context" => parsed.context = Some(args.parsed_value()?),
+            "threads" => config.threads = args.parsed_value()?,
+            "color" | "colour" => config.color = args.parsed_value()?,
+            "with-filename" => config.with_filename = Some(true),
+            "no-filename" => config.with_filename = Some(false),
+            "json" => config.json = true,
+            "fuzzy" => config.fuzzy = Some(args.parsed_value()?),
+            "replace" => config.replace = Some(args.value()?),
+            "in-place" => {
+                config.in_place = true;
+                config.backup_suffix = args.optional_value();
+            }
+            "no-mmap" => config.no_mmap = true,
+            "encoding" => config.encoding = Some(args.parsed_value()?),
+            "type" => config.types.push(args.value()?),
+            "type-not" => config.types_not.push(args.value()?),
+            "glob" => config.globs.push(args.value()?),
+            "interactive" => config.interactive = true,
+            "no-index" => config.no_index = true,
+            "binary-files" => config.binary_files = args.parsed_value()?,
+            "text" => config.binary_files = BinaryFiles::Text,
+            // Handled before parsing
+            "no-config" => parsed.no_config = true,
+            "help" => return Err(ArgsError::Help),
+            _ => return Err(ArgsError::UnknownOption(format!("--{}", name))),
+        }
+    }
+    Ok(())
+}
+
+// What the options parsed so far set, besides the fields of Config
+#[derive(Default)]
+struct Parsed {
+    positional: Vec<String>,
+    // Patterns given with -e or -f replace the query argument
+    patterns_given: bool,
+    after_context: Option<usize>,
+    before_context: Option<usize>,
+    context: Option<usize>,
+    no_config: bool,
+}
 
-        Ok(Config { query, filename, case_sensitive })
+impl Parsed {
+    // -A and -B take precedence over -C, whatever their order. The context from the command line
+    // replaces the context from the config file as a whole, so they are set after each of them.
+    fn resolve_context(&mut self, config: &mut Config) {
+        if let Some(after) = self.after_context.take().or(self.context) {
+            config.after_context = after;
+        }
+        if let Some(before) = self.before_context.take().or(self.context) {
+            config.before_context = before;
+        }
+        self.context = None;
     }
 }
 
-// Box<dyn Error> is a type that implements the Error trait
-pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    let contents = fs::read_to_string(config.filename)?;
-    println!("With text: \n{}\n", contents);
+// Reads one pattern per line. An empty file has no patterns, so nothing matches.
+fn read_patterns(path: &str) -> Result<Vec<String>, ArgsError> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| ArgsError::PatternFile { path: String::from(path), message: e.to_string() })?;
+    Ok(contents.lines().map(String::from).collect())
+}
 
-    let result = if config.case_sensitive {
-        search(&config.query, &contents)
+// Fails with NoMatch when no line was selected. Files that can't be read are reported on stderr
+// as they come up, the others are still searched, and then it fails with Skipped.
+pub fn run(mut config: Config) -> Result<(), MinigrepError> {
+    let started = Instant::now();
+    let files_only = config.files_with_matches || config.files_without_match;
+    // Context lines are not needed when only counting or printing matches or paths, and they
+    // would break the format of --vimgrep
+    if (config.count || config.only_matching || config.vimgrep || files_only) && !config.json {
+        config.before_context = 0;
+        config.after_context = 0;
+    }
+    // The first match is enough to know a file matches
+    if files_only && !config.json {
+        config.max_count = Some(config.max_count.unwrap_or(1).min(1));
+    }
+    let searcher = Searcher::from_config(&config)?;
+    config.color = config.color.resolve();
+
+    let filter = Filter::new(&config.types, &config.types_not, &config.globs)?;
+    if config.interactive {
+        return tui::run(config, &filter);
+    }
+    // Files in an indexed directory that can't contain the query are not read
+    let query = if config.no_index { None } else { index::Query::new(&config) };
+    let mut inputs = Vec::new();
+    let mut errors = Vec::new();
+    for filename in &config.filenames {
+        let path = Path::new(filename);
+        let mut found = self::inputs(path, &filter, &mut errors);
+        // An index that can't be read is not used, the search just takes longer
+        if let (Some(query), Ok(Some(index))) = (&query, Index::open(path)) {
+            for input in &mut found {
+                input.cannot_match = !index.may_match(path, &input.path, query);
+            }
+        }
+        inputs.extend(found);
+    }
+    // Unless -n or -N says otherwise, lines found in a directory are numbered. Matches printed
+    // with -o are not, like the counts of -c.
+    if config.line_number.is_none() && !config.only_matching && inputs.iter().any(|input| input.from_dir) {
+        config.line_number = Some(true);
+    }
+    for error in &errors {
+        report(error);
+    }
+    let mut skipped = errors.len();
+
+    if config.in_place {
+        let replacement = Replacement::new(config.replace.as_deref().unwrap_or_default(), config.regex);
+        for input in &inputs {
+            if let Err(error) = edit_in_place(&config, searcher.matcher(), &replacement, input) {
+                report(&MinigrepError::file(&input.path, error));
+                skipped += 1;
+            }
+        }
+        return if skipped > 0 { Err(MinigrepError::Skipped(skipped)) } else { Ok(()) };
+    }
+
+    // Like grep, only prefix lines with the path when there can be more than one file
+    if config.with_filename.is_none() {
+        config.with_filename = Some(config.filenames.len() > 1 || inputs.iter().any(|input| input.from_dir));
+    }
+    let json = config.json;
+    let files_without_match = config.files_without_match && !json;
+
+    // Lock stdout once and buffer it, instead of locking and flushing it for every line
+    let mut out = BufWriter::new(io::stdout().lock());
+
+    // A single file is streamed straight to stdout, so its size doesn't matter
+    let stats = if inputs.len() == 1 {
+        let result = search_path(&searcher, new_sink(&config, searcher.matcher(), &mut out).as_mut(), &inputs[0]);
+        result.unwrap_or_else(|error| {
+            report(&MinigrepError::file(&inputs[0].path, error));
+            skipped += 1;
+            Stats::default()
+        })
     } else {
-        search_case_insensitive(&config.query, &contents)
+        let (stats, failed) = run_parallel(Arc::new(config), Arc::new(searcher), inputs, &mut out)?;
+        skipped += failed;
+        stats
     };
 
-    for line in result {
-        println!("{}", line);
+    if json {
+        json::write_summary(&mut out, &stats, started.elapsed())?;
+    }
+    out.flush()?;
+
+    // With -L, a line is selected in the files that are listed
+    let selected = if files_without_match { stats.searches_with_match < stats.searches } else { stats.matched_lines > 0 };
+    if skipped > 0 {
+        Err(MinigrepError::Skipped(skipped))
+    } else if !selected {
+        Err(MinigrepError::NoMatch)
+    } else {
+        Ok(())
+    }
+}
+
+// Errors that don't stop the search go to stderr right away, like grep does
+fn report(error: &MinigrepError) {
+    eprintln!("minigrep: {}", error);
+}
+
+fn new_sink<'a, W: Write + 'a>(config: &Config, matcher: &'a Matcher, out: W) -> Box<dyn Sink + 'a> {
+    if config.json {
+        Box::new(JsonPrinter::new(matcher, out))
+    } else {
+        Box::new(Printer::new(config, matcher, out))
+    }
+}
+
+// A file to search. Files found by walking a directory are skipped quietly when they are compressed
+// in a format that can't be decoded, and are not edited in place when they are binary.
+struct Input {
+    path: PathBuf,
+    from_dir: bool,
+    // The directory's index shows that the file can't contain the query
+    cannot_match: bool,
+}
+
+// The file itself, or the files found in a directory
+fn inputs(path: &Path, filter: &Filter, errors: &mut Vec<MinigrepError>) -> Vec<Input> {
+    if path.is_dir() {
+        walk::walk(path, filter, errors).into_iter().map(|path| Input { path, from_dir: true, cannot_match: false }).collect()
+    } else {
+        vec![Input { path: path.to_path_buf(), from_dir: false, cannot_match: false }]
+    }
+}
+
+// Searches the inputs on a thread pool. Each file's output is collected in its own buffer
+// and the buffers are written in the order of the inputs, so the output is always the same.
+// Returns the totals and the number of files that couldn't be searched.
+fn run_parallel<W: Write>(config: Arc<Config>, searcher: Arc<Searcher>, inputs: Vec<Input>, out: &mut W) -> io::Result<(Stats, usize)> {
+    let threads = match config.threads {
+        0 => thread::available_parallelism().map_or(1, |n| n.get()),
+        threads => threads,
+    };
+    let pool = ThreadPool::new(threads.min(inputs.len()).max(1));
+    let (sender, receiver) = mpsc::channel();
+    // Set when the output can't be written, the searches that haven't started then don't run
+    let cancelled = Arc::new(AtomicBool::new(false));
+
+    for (index, input) in inputs.into_iter().enumerate() {
+        let config = Arc::clone(&config);
+        let searcher = Arc::clone(&searcher);
+        let sender = sender.clone();
+        let cancelled = Arc::clone(&cancelled);
+
+        pool.execute(move || {
+            if cancelled.load(Ordering::Relaxed) {
+                return;
+            }
+            let mut output = Vec::new();
+            // A search that panics is reported like a file that couldn't be read, so every index is
+            // sent and the files after it are still written out
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                search_path(&searcher, new_sink(&config, searcher.matcher(), &mut output).as_mut(), &input)
+            }));
+            let result = result
+                .unwrap_or_else(|_| Err(io::Error::other("the search panicked")))
+                .map_err(|error| MinigrepError::file(&input.path, error));
+            // Sending only fails when writing the output failed and nobody is listening anymore
+            let _ = sender.send((index, result.map(|stats| (output, stats))));
+        });
+    }
+    // The loop below ends once every job has dropped its sender
+    drop(sender);
+
+    let separate_groups = !config.json && (config.before_context > 0 || config.after_context > 0);
+    let mut pending = HashMap::new();
+    let mut next = 0;
+    let mut printed = false;
+    let mut total = Stats::default();
+    let mut failed = 0;
+
+    for (index, result) in receiver {
+        pending.insert(index, result);
+
+        // Write out every finished file that all the earlier files are waiting on
+        while let Some(result) = pending.remove(&next) {
+            next += 1;
+            let (output, stats) = match result {
+                Ok(result) => result,
+                Err(error) => {
+                    report(&error);
+                    failed += 1;
+                    continue;
+                }
+            };
+            let separator = if separate_groups && printed && !output.is_empty() { writeln!(out, "--") } else { Ok(()) };
+            // E.g. a closed pipe: dropping the pool waits for the jobs, so they are cancelled first
+            if let Err(error) = separator.and_then(|_| out.write_all(&output)) {
+                cancelled.store(true, Ordering::Relaxed);
+                return Err(error);
+            }
+            printed |= !output.is_empty();
+            total.add(&stats);
+        }
+    }
+
+    Ok((total, failed))
+}
+
+fn search_path(searcher: &Searcher, sink: &mut dyn Sink, input: &Input) -> io::Result<Stats> {
+    // Compressed input is searched decompressed
+    if input.path == Path::new("-") {
+        return searcher.search_reader(Path::new("(standard input)"), decompress::decoder(io::stdin().lock())?, sink);
+    }
+    if !input.from_dir {
+        return searcher.search_path(&input.path, sink);
+    }
+    // Reported like a file without a match
+    if input.cannot_match {
+        let stats = Stats { searches: 1, ..Stats::default() };
+        sink.begin(&input.path)?;
+        sink.end(&input.path, &stats)?;
+        return Ok(stats);
+    }
+
+    match searcher.search_path(&input.path, sink) {
+        // Files compressed in a format we can't decode are skipped quietly, corrupt ones are errors
+        Err(e) if e.kind() == io::ErrorKind::Unsupported => Ok(Stats::default()),
+        result => result,
+    }
+}
+
+// Rewrites the file with every match replaced. Files that don't change are not touched.
+fn edit_in_place(config: &Config, matcher: &Matcher, replacement: &Replacement, input: &Input) -> io::Result<()> {
+    if input.path == Path::new("-") {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "standard input can't be edited in place"));
     }
 
+    let bytes = fs::read(&input.path)?;
+    if input.from_dir && binary::is_binary(&bytes) {
+        return Ok(());
+    }
+    let contents = match String::from_utf8(bytes) {
+        Ok(contents) => contents,
+        Err(_) if input.from_dir => return Ok(()),
+        Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+    };
+
+    let replaced = replace::replace_lines(matcher, replacement, &contents);
+    if replaced != contents {
+        replace::write_atomically(&input.path, &replaced, config.backup_suffix.as_deref())?;
+    }
     Ok(())
 }
 
 // We tell Rust that the data returned by the search function will live as long as
 // the data passed into the search function in the contents argument.
+// The contents are searched as a whole, and only the lines around the matches are looked at
 pub fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
-    contents.lines().filter(|line| line.contains(query)).collect()
+    // Lines don't contain their line ending, so such a query is checked line by line
+    if query.contains(['\n', '\r']) {
+        return contents.lines().filter(|line| line.contains(query)).collect();
+    }
+
+    let finder = Finder::new(query.as_bytes());
+    buffer::LinesWith::new(&finder, contents.as_bytes())
+        .map(|(start, end)| {
+            let line = &contents[start..end];
+            line.strip_suffix('\r').unwrap_or(line)
+        })
+        .collect()
 }
 
+// Compares case-folded characters instead of lowercasing every line, see casefold
 pub fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
-    contents.lines().filter(|line| line.to_lowercase().contains(&query.to_lowercase())).collect()
+    let query = FoldedQuery::new(query);
+    contents.lines().filter(|line| query.find_at(line, 0).is_some()).collect()
+}
+
+// Returns the lines within `max_distance` edits of the query, with the distance of their closest part
+pub fn search_fuzzy<'a>(query: &str, max_distance: usize, contents: &'a str) -> Vec<(&'a str, usize)> {
+    let fuzzy = Fuzzy::new(query, max_distance, false);
+    contents.lines()
+        .filter_map(|line| fuzzy.best_match(line).map(|(_, _, distance)| (line, distance)))
+        .collect()
+}
+
+pub fn search_regex<'a>(regex: &Regex, contents: &'a str) -> Vec<&'a str> {
+    contents.lines().filter(|line| regex.is_match(line)).collect()
+}
+
+// Returns the matching lines together with their line numbers, starting from 1
+pub fn search_lines<'a>(matcher: &Matcher, contents: &'a str) -> Vec<(usize, &'a str)> {
+    contents.lines()
+        .enumerate()
+        .filter(|(_, line)| matcher.is_match(line))
+        .map(|(index, line)| (index + 1, line))
+        .collect()
 }
 
 #[cfg(test)]
@@ -67,40 +612,311 @@ mod tests {
     use super::*;
 
     #[test]
-    #[should_panic(expected = "Didn't get a query string")]
+    #[should_panic(expected = "MissingQuery")]
     fn config_new_without_query_should_fail() {
         let args = vec!(String::from("program name"));
         Config::new(args.into_iter()).unwrap();
     }
 
     #[test]
-    #[should_panic(expected = "Didn't get a file name")]
-    fn config_new_without_filename_should_fail() {
+    fn config_new_without_filename_should_read_stdin() {
         let args = vec!(String::from("program name"), String::from("text"));
-        Config::new(args.into_iter()).unwrap();
+        let result = Config::new(args.into_iter()).unwrap();
+        assert_eq!(result.filenames, vec!["-"]);
     }
 
     #[test]
     fn config_new_with_3_args_should_pass() {
         let args = vec!(String::from("program name"), String::from("text"), String::from("filename"));
         let result = Config::new(args.into_iter()).unwrap();
-        assert_eq!(result, Config { query: String::from("text"), filename: String::from("filename"), case_sensitive: true });
+        assert_eq!(result, Config { patterns: vec![String::from("text")], filenames: vec![String::from("filename")], case_sensitive: true, ..Config::default() });
+    }
+
+    #[test]
+    fn config_new_with_regex_flag_should_pass() {
+        let args = vec!(String::from("program name"), String::from("--regex"), String::from("^t.xt$"), String::from("filename"));
+        let result = Config::new(args.into_iter()).unwrap();
+        assert_eq!(result, Config { patterns: vec![String::from("^t.xt$")], filenames: vec![String::from("filename")], case_sensitive: true, regex: true, ..Config::default() });
+    }
+
+    #[test]
+    fn config_new_with_flags_should_pass() {
+        let args = vec!["program name", "-inc", "--invert-match", "-w", "--no-mmap", "--", "-text", "filename"];
+        let result = Config::new(args.into_iter().map(String::from)).unwrap();
+        let expected = Config {
+            patterns: vec![String::from("-text")],
+            filenames: vec![String::from("filename")],
+            case_sensitive: false,
+            invert_match: true,
+            line_number: Some(true),
+            count: true,
+            word_regexp: true,
+            no_mmap: true,
+            ..Config::default()
+        };
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn config_new_with_patterns_should_pass() {
+        let args = vec!["program name", "-e", "rust", "--regexp=duct", "poem.txt"];
+        let result = Config::new(args.into_iter().map(String::from)).unwrap();
+        assert_eq!((result.patterns, result.filenames), (vec![String::from("rust"), String::from("duct")], vec![String::from("poem.txt")]));
+    }
+
+    #[test]
+    fn config_new_with_output_modes_should_pass() {
+        let args = vec!["program name", "-olL", "--max-count", "3", "nobody", "poem.txt"];
+        let result = Config::new(args.into_iter().map(String::from)).unwrap();
+        let modes = (result.only_matching, result.files_with_matches, result.files_without_match, result.max_count);
+        assert_eq!((true, true, true, Some(3)), modes);
+    }
+
+    #[test]
+    fn config_new_with_multiline_should_pass() {
+        let args = vec!["program name", "-U", "--regex", "a\\nb", "poem.txt"];
+        assert!(Config::new(args.into_iter().map(String::from)).unwrap().multiline);
+
+        let args = vec!["program name", "--multiline", "-r", "x", "--in-place", "a", "poem.txt"];
+        let result = Config::new(args.into_iter().map(String::from));
+        assert_eq!(result, Err(ArgsError::ConflictingOptions(String::from("multiline"), String::from("in-place"))));
+    }
+
+    #[test]
+    fn config_new_with_pattern_file_should_pass() {
+        let path = env::temp_dir().join(format!("minigrep-patterns-{}", std::process::id()));
+        fs::write(&path, "nobody\nfrog\n").unwrap();
+        let args = vec![String::from("program name"), String::from("-f"), path.display().to_string(), String::from("-e"), String::from("bog")];
+        let result = Config::new(args.into_iter()).unwrap();
+        fs::remove_file(path).unwrap();
+        assert_eq!((result.patterns, result.filenames), (vec![String::from("nobody"), String::from("frog"), String::from("bog")], vec![String::from("-")]));
+
+        let args = vec!["program name", "-f", "no-such-patterns.txt", "poem.txt"];
+        let result = Config::new(args.into_iter().map(String::from));
+        assert!(matches!(result, Err(ArgsError::PatternFile { .. })));
+    }
+
+    #[test]
+    fn config_new_with_replace_should_pass() {
+        let args = vec!["program name", "-r", "$1", "--in-place=.orig", "text", "filename"];
+        let result = Config::new(args.into_iter().map(String::from)).unwrap();
+        assert_eq!((result.replace, result.in_place, result.backup_suffix), (Some(String::from("$1")), true, Some(String::from(".orig"))));
+
+        let args = vec!["program name", "--in-place", "text", "filename"];
+        let result = Config::new(args.into_iter().map(String::from));
+        assert_eq!(result, Err(ArgsError::RequiresOption { option: String::from("in-place"), required: String::from("replace") }));
+    }
+
+    #[test]
+    fn run_in_place_rewrites_files() {
+        let dir = env::temp_dir().join(format!("minigrep-in-place-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.txt"), "I'm nobody! Who are you?\nAre you nobody, too?\n").unwrap();
+        fs::write(dir.join("b.txt"), "Then there's a pair of us\n").unwrap();
+
+        let args = vec![String::from("program name"), String::from("--regex"), String::from("-r"), String::from("some$1"),
+                        String::from("--in-place"), String::from("no(body)"), dir.display().to_string()];
+        run(Config::new(args.into_iter()).unwrap()).unwrap();
+
+        assert_eq!("I'm somebody! Who are you?\nAre you somebody, too?\n", fs::read_to_string(dir.join("a.txt")).unwrap());
+        assert_eq!("Then there's a pair of us\n", fs::read_to_string(dir.join("b.txt")).unwrap());
+        assert_eq!(2, fs::read_dir(&dir).unwrap().count());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn config_new_with_context_should_pass() {
+        let args = vec!["program name", "-C", "2", "-A1", "text", "filename"];
+        let result = Config::new(args.into_iter().map(String::from)).unwrap();
+        assert_eq!((result.before_context, result.after_context), (2, 1));
+    }
+
+    #[test]
+    fn config_new_with_invalid_context_should_fail() {
+        let args = vec!["program name", "--context=many", "text", "filename"];
+        let result = Config::new(args.into_iter().map(String::from));
+        let expected = ArgsError::InvalidValue { option: String::from("context"), value: String::from("many") };
+        assert_eq!(result, Err(expected));
+    }
+
+    #[test]
+    fn config_new_with_encoding_should_pass() {
+        let args = vec!["program name", "--encoding", "utf-16le", "text", "filename"];
+        let result = Config::new(args.into_iter().map(String::from)).unwrap();
+        assert_eq!(Some(Encoding::Utf16Le), result.encoding);
+
+        let args = vec!["program name", "--encoding=ebcdic", "text", "filename"];
+        let result = Config::new(args.into_iter().map(String::from));
+        let expected = ArgsError::InvalidValue { option: String::from("encoding"), value: String::from("ebcdic") };
+        assert_eq!(result, Err(expected));
+    }
+
+    #[test]
+    fn config_new_with_positions_should_pass() {
+        let args = vec!["program name", "--column", "-nb", "text", "filename"];
+        let result = Config::new(args.into_iter().map(String::from)).unwrap();
+        assert_eq!((true, true, true, false), (result.line_number == Some(true), result.column, result.byte_offset, result.vimgrep));
+        let args = vec!["program name", "--vimgrep", "text", "filename"];
+        let result = Config::new(args.into_iter().map(String::from)).unwrap();
+        assert!(result.vimgrep);
+    }
+
+    #[test]
+    fn config_new_with_line_number_flags_should_pass() {
+        let config = |args: Vec<&str>| Config::new(args.into_iter().map(String::from)).unwrap().line_number;
+        assert_eq!(None, config(vec!["program name", "text", "src"]));
+        assert_eq!(Some(false), config(vec!["program name", "-N", "text", "src"]));
+        assert_eq!(Some(true), config(vec!["program name", "-N", "-n", "text", "src"]));
+    }
+
+    #[test]
+    fn config_new_with_interactive_should_pass() {
+        let args = vec!["program name", "--interactive", "-e", "frog"];
+        let result = Config::new(args.into_iter().map(String::from)).unwrap();
+        assert_eq!((vec![String::from("frog")], vec![String::from(".")]), (result.patterns, result.filenames));
+
+        let args = vec!["program name", "--interactive", "-e", "foo", "-e", "bar"];
+        let result = Config::new(args.into_iter().map(String::from));
+        assert_eq!(result, Err(ArgsError::SinglePattern(String::from("interactive"))));
+    }
+
+    #[test]
+    fn config_new_with_build_index_should_pass() {
+        let args = vec!["program name", "index", "build", "src"];
+        let result = Config::new(args.into_iter().map(String::from)).unwrap();
+        assert_eq!((Some(String::from("src")), Vec::<String>::new()), (result.build_index, result.patterns));
+        // Searches for "index"
+        for args in [vec!["program name", "--", "index", "build", "src"], vec!["program name", "index", "build", "src", "tests"]] {
+            let result = Config::new(args.into_iter().map(String::from)).unwrap();
+            assert_eq!((None, vec![String::from("index")]), (result.build_index, result.patterns));
+        }
+    }
+
+    #[test]
+    fn config_new_with_binary_files_should_pass() {
+        let args = vec!["program name", "--binary-files=without-match", "text", "filename"];
+        let result = Config::new(args.into_iter().map(String::from)).unwrap();
+        assert_eq!(BinaryFiles::WithoutMatch, result.binary_files);
+        let args = vec!["program name", "-a", "text", "filename"];
+        let result = Config::new(args.into_iter().map(String::from)).unwrap();
+        assert_eq!(BinaryFiles::Text, result.binary_files);
+
+        let args = vec!["program name", "--binary-files", "hex", "text", "filename"];
+        let result = Config::new(args.into_iter().map(String::from));
+        let expected = ArgsError::InvalidValue { option: String::from("binary-files"), value: String::from("hex") };
+        assert_eq!(result, Err(expected));
+    }
+
+    #[test]
+    fn config_file_sets_defaults() {
+        let path = env::temp_dir().join(format!("minigrep-config-{}", std::process::id()));
+        fs::write(&path, "# Defaults\n--ignore-case\n-C 2\n--glob !target/**\n").unwrap();
+        let config = |args: Vec<&str>| Config::with_config_file(args.into_iter().map(String::from), Some(&path));
+
+        let result = config(vec!["program name", "text", "filename"]).unwrap();
+        assert_eq!((false, 2, 2, vec![String::from("!target/**")]), (result.case_sensitive, result.before_context, result.after_context, result.globs));
+        // The command line overrides the defaults, -A even overrides a default -C
+        let result = config(vec!["program name", "-s", "-A1", "text", "filename"]).unwrap();
+        assert_eq!((true, 2, 1), (result.case_sensitive, result.before_context, result.after_context));
+        let result = config(vec!["program name", "--no-config", "text", "filename"]).unwrap();
+        assert_eq!((0, Vec::<String>::new()), (result.after_context, result.globs));
+        // A pattern or file named --no-config is not the option
+        for args in [vec!["program name", "-e", "--no-config", "filename"], vec!["program name", "text", "--", "--no-config"]] {
+            assert_eq!(2, config(args).unwrap().after_context);
+        }
+
+        fs::write(&path, "--ignore-case\n\n--colour-me-surprised\n").unwrap();
+        let result = config(vec!["program name", "text", "filename"]);
+        let expected = ArgsError::InConfigFile {
+            path: path.display().to_string(),
+            line: 3,
+            error: Box::new(ArgsError::UnknownOption(String::from("--colour-me-surprised"))),
+        };
+        assert_eq!(result, Err(expected));
+
+        fs::write(&path, "-e nobody\n").unwrap();
+        let result = config(vec!["program name", "text", "filename"]);
+        assert!(matches!(result, Err(ArgsError::InConfigFile { error, .. }) if *error == ArgsError::NotInConfigFile(String::from("-e nobody"))));
+        fs::remove_file(&path).unwrap();
+
+        let result = config(vec!["program name", "text", "filename"]);
+        assert!(matches!(result, Err(ArgsError::ConfigFile { .. })));
+    }
+
+    #[test]
+    fn config_new_with_color_should_pass() {
+        let args = vec!["program name", "--color=always", "text"];
+        let result = Config::new(args.into_iter().map(String::from)).unwrap();
+        assert_eq!(result.color, ColorChoice::Always);
+
+        let args = vec!["program name", "--color", "rainbow", "text"];
+        let result = Config::new(args.into_iter().map(String::from));
+        let expected = ArgsError::InvalidValue { option: String::from("color"), value: String::from("rainbow") };
+        assert_eq!(result, Err(expected));
+    }
+
+    #[test]
+    fn config_new_case_sensitive_flag_wins_over_ignore_case() {
+        let args = vec!["program name", "-i", "--case-sensitive", "text", "filename"];
+        let result = Config::new(args.into_iter().map(String::from)).unwrap();
+        assert!(result.case_sensitive);
+    }
+
+    #[test]
+    fn config_new_with_help_should_fail() {
+        let args = vec!["program name", "text", "--help"];
+        let result = Config::new(args.into_iter().map(String::from));
+        assert_eq!(result, Err(ArgsError::Help));
+    }
+
+    #[test]
+    fn config_new_with_unknown_option_should_fail() {
+        let args = vec!["program name", "--colr", "text", "filename"];
+        let result = Config::new(args.into_iter().map(String::from));
+        assert_eq!(result, Err(ArgsError::UnknownOption(String::from("--colr"))));
+    }
+
+    #[test]
+    fn config_new_with_several_files_should_pass() {
+        let args = vec!["program name", "text", "a.txt", "-j", "2", "b.txt"];
+        let result = Config::new(args.into_iter().map(String::from)).unwrap();
+        assert_eq!((result.filenames, result.threads), (vec![String::from("a.txt"), String::from("b.txt")], 2));
+    }
+
+    #[test]
+    fn run_with_invalid_regex_should_fail() {
+        let args = vec!(String::from("program name"), String::from("--regex"), String::from("(nobody"), String::from("poem.txt"));
+        let result = Config::new(args.into_iter()).unwrap();
+        assert!(run(result).is_err());
     }
 
     #[test]
-    #[should_panic(expected = "NotFound")]
     fn run_with_invalid_filename_should_fail() {
-        let args = vec!(String::from("program name"), String::from("text"), String::from("filename"));
+        let args = vec!(String::from("program name"), String::from("nobody"), String::from("filename"), String::from("poem.txt"));
         let result = Config::new(args.into_iter()).unwrap();
-        run(result).unwrap();
+        // poem.txt is still searched
+        let error = run(result).unwrap_err();
+        assert!(matches!(error, MinigrepError::Skipped(1)));
+        assert_eq!(2, error.exit_code());
     }
 
     #[test]
     fn run_with_valid_filename_should_pass() {
+        let args = vec!(String::from("program name"), String::from("nobody"), String::from("poem.txt"));
+        let result = Config::new(args.into_iter()).unwrap();
+        assert!(run(result).is_ok());
+    }
+
+    #[test]
+    fn run_without_match_should_fail() {
         let args = vec!(String::from("program name"), String::from("text"), String::from("poem.txt"));
         let result = Config::new(args.into_iter()).unwrap();
-        let response = run(result).unwrap();
-        assert_eq!(response, ());
+        let error = run(result).unwrap_err();
+        assert!(matches!(error, MinigrepError::NoMatch));
+        assert_eq!(1, error.exit_code());
+
+        let args = vec!(String::from("program name"), String::from("-L"), String::from("text"), String::from("poem.txt"));
+        assert!(run(Config::new(args.into_iter()).unwrap()).is_ok());
     }
 
     #[test]
@@ -137,4 +953,249 @@ Trust me.";
 
         assert_eq!(vec!["Rust:", "Trust me."], search_case_insensitive(query, contents))
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn case_insensitive_unicode() {
+        let query = "STRASSE";
+        let contents = "\
+Hauptstraße 1
+HAUPTSTRAẞE 2
+Strase 3";
+
+        assert_eq!(vec!["Hauptstraße 1", "HAUPTSTRAẞE 2"], search_case_insensitive(query, contents))
+    }
+
+    #[test]
+    fn search_with_typos() {
+        let query = "nobody";
+        let contents = "\
+I'm nobody! Who are you?
+Are you nobdy, too?
+Then there's a pair of us - don't tell!";
+
+        assert_eq!(vec![("I'm nobody! Who are you?", 0), ("Are you nobdy, too?", 1)], search_fuzzy(query, 1, contents));
+    }
+
+    #[test]
+    fn config_new_with_fuzzy_should_pass() {
+        let args = vec!["program name", "--fuzzy", "2", "text"];
+        let result = Config::new(args.into_iter().map(String::from)).unwrap();
+        assert_eq!(result.fuzzy, Some(2));
+
+        let args = vec!["program name", "--fuzzy=1", "--regex", "text"];
+        let result = Config::new(args.into_iter().map(String::from));
+        assert_eq!(result, Err(ArgsError::ConflictingOptions(String::from("fuzzy"), String::from("regex"))));
+    }
+
+    #[test]
+    fn search_with_regex() {
+        let regex = Regex::new(r"^(safe|Pick) \w+").unwrap();
+        let contents = "\
+Rust:
+safe, fast, productive.
+Pick three.
+Duct tape.";
+
+        assert_eq!(vec!["Pick three."], search_regex(&regex, contents));
+    }
+
+    #[test]
+    fn search_with_case_insensitive_regex() {
+        let regex = regex::RegexBuilder::new("^[a-z]+ t").case_insensitive(true).build().unwrap();
+        let contents = "\
+Rust:
+safe, fast, productive.
+Pick three.
+Duct tape.";
+
+        assert_eq!(vec!["Pick three.", "Duct tape."], search_regex(&regex, contents));
+    }
+
+    #[test]
+    fn search_lines_returns_line_numbers() {
+        let config = Config { patterns: vec![String::from("us")], case_sensitive: true, ..Config::default() };
+        let matcher = Matcher::new(&config).unwrap();
+        let contents = "\
+Rust:
+safe, fast, productive.
+Pick three.
+Trust me.";
+
+        assert_eq!(vec![(1, "Rust:"), (4, "Trust me.")], search_lines(&matcher, contents));
+    }
+
+    // Searches the files with the sink for the config, the way run does
+    fn search_output(config: Config, paths: &[&str]) -> String {
+        let config = Config { case_sensitive: true, ..config };
+        let searcher = Searcher::from_config(&config).unwrap();
+        let mut output = Vec::new();
+        let mut printer = Printer::new(&config, searcher.matcher(), &mut output);
+        for path in paths {
+            search_path(&searcher, &mut printer, &Input { path: PathBuf::from(path), from_dir: false, cannot_match: false }).unwrap();
+        }
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn search_path_prints_only_matching() {
+        let config = Config { patterns: vec![String::from("body")], only_matching: true, line_number: Some(true), ..Config::default() };
+        assert_eq!("1:body\n2:body\n6:body\n", search_output(config, &["poem.txt"]));
+    }
+
+    #[test]
+    fn search_path_prints_files_with_and_without_matches() {
+        let config = Config { patterns: vec![String::from("nobody")], files_with_matches: true, max_count: Some(1), ..Config::default() };
+        assert_eq!("poem.txt\n", search_output(config, &["poem.txt", "Cargo.toml"]));
+        let config = Config { patterns: vec![String::from("nobody")], files_without_match: true, max_count: Some(1), ..Config::default() };
+        assert_eq!("Cargo.toml\n", search_output(config, &["poem.txt", "Cargo.toml"]));
+    }
+
+    #[test]
+    fn search_path_stops_at_max_count() {
+        let config = Config { patterns: vec![String::from("body")], max_count: Some(2), count: true, ..Config::default() };
+        assert_eq!("2\n", search_output(config, &["poem.txt"]));
+        let config = Config { patterns: vec![String::from("body")], max_count: Some(1), ..Config::default() };
+        assert_eq!("I'm nobody! Who are you?\n", search_output(config, &["poem.txt"]));
+    }
+
+    #[test]
+    fn search_path_decompresses_gzip() {
+        let config = Config { patterns: vec![String::from("frog")], case_sensitive: true, line_number: Some(true), ..Config::default() };
+        let searcher = searcher_for("frog");
+        let mut output = Vec::new();
+        let mut printer = Printer::new(&config, searcher.matcher(), &mut output);
+        for path in ["poem.txt.gz", "poem.txt"] {
+            search_path(&searcher, &mut printer, &Input { path: PathBuf::from(path), from_dir: true, cannot_match: false }).unwrap();
+        }
+        assert_eq!("7:How public, like a frog\n7:How public, like a frog\n", String::from_utf8(output).unwrap());
+    }
+
+    #[test]
+    fn search_path_reports_corrupt_files_in_directories() {
+        let dir = env::temp_dir().join(format!("minigrep-corrupt-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut corrupt = fs::read("poem.txt.gz").unwrap();
+        // The CRC-32 in the trailer, so the error comes after the lines were searched
+        let end = corrupt.len() - 8;
+        corrupt[end] ^= 1;
+        fs::write(dir.join("corrupt.gz"), corrupt).unwrap();
+        fs::write(dir.join("poem.zst"), [0x28, 0xb5, 0x2f, 0xfd, 0x00]).unwrap();
+        fs::write(dir.join("bzh.txt"), "BZh is a prefix\nfrog\n").unwrap();
+
+        let searcher = searcher_for("frog");
+        let search = |name: &str| {
+            let mut output = Vec::new();
+            let mut printer = Printer::new(&Config::default(), searcher.matcher(), &mut output);
+            search_path(&searcher, &mut printer, &Input { path: dir.join(name), from_dir: true, cannot_match: false }).map(|_| output)
+        };
+        assert_eq!(io::ErrorKind::InvalidData, search("corrupt.gz").unwrap_err().kind());
+        assert_eq!(Vec::<u8>::new(), search("poem.zst").unwrap());
+        assert_eq!(b"frog\n".to_vec(), search("bzh.txt").unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn search_of_an_indexed_directory_leaves_out_the_index() {
+        let dir = env::temp_dir().join(format!("minigrep-indexed-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("poem.txt"), "I'm nobody! Who are you?\n").unwrap();
+        Index::build(&dir).unwrap();
+
+        let inputs = inputs(&dir, &Filter::default(), &mut Vec::new());
+        assert_eq!(vec![dir.join("poem.txt")], inputs.iter().map(|input| input.path.clone()).collect::<Vec<_>>());
+        let config = Config { patterns: vec![String::from("poem")], with_filename: Some(true), ..Config::default() };
+        let searcher = Searcher::from_config(&config).unwrap();
+        let mut output = Vec::new();
+        let mut printer = Printer::new(&config, searcher.matcher(), &mut output);
+        for input in &inputs {
+            search_path(&searcher, &mut printer, input).unwrap();
+        }
+        assert_eq!("", String::from_utf8(output).unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn run_parallel_keeps_input_order() {
+        let config = Config { patterns: vec![String::from("o")], case_sensitive: true, threads: 4, with_filename: Some(true), ..Config::default() };
+        let inputs: Vec<_> = ["poem.txt", "Cargo.toml", "poem.txt", "src/main.rs", "poem.txt"].iter()
+            .map(|path| Input { path: PathBuf::from(path), from_dir: false, cannot_match: false })
+            .collect();
+
+        let mut expected = Vec::new();
+        let searcher = searcher_for("o");
+        let mut printer = Printer::new(&config, searcher.matcher(), &mut expected);
+        for input in &inputs {
+            search_path(&searcher, &mut printer, input).unwrap();
+        }
+
+        let mut output = Vec::new();
+        run_parallel(Arc::new(config), Arc::new(searcher_for("o")), inputs, &mut output).unwrap();
+        assert_eq!(String::from_utf8(expected).unwrap(), String::from_utf8(output).unwrap());
+    }
+
+    #[test]
+    fn run_parallel_continues_past_errors() {
+        let config = Config { patterns: vec![String::from("nobody")], case_sensitive: true, with_filename: Some(true), ..Config::default() };
+        let inputs: Vec<_> = ["poem.txt", "missing.txt", "poem.txt"].iter()
+            .map(|path| Input { path: PathBuf::from(path), from_dir: false, cannot_match: false })
+            .collect();
+
+        let mut output = Vec::new();
+        let (stats, failed) = run_parallel(Arc::new(config), Arc::new(searcher_for("nobody")), inputs, &mut output).unwrap();
+        assert_eq!((2, 4, 1), (stats.searches, stats.matched_lines, failed));
+        let lines = "poem.txt:I'm nobody! Who are you?\npoem.txt:Are you nobody, too?\n";
+        assert_eq!(lines.repeat(2), String::from_utf8(output).unwrap());
+    }
+
+    // Output that can't be written, like a closed pipe
+    struct Closed;
+
+    impl Write for Closed {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::BrokenPipe, "closed"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn run_parallel_stops_when_the_output_is_closed() {
+        let config = Config { patterns: vec![String::from("nobody")], threads: 1, ..Config::default() };
+        let inputs: Vec<_> = (0..1000).map(|_| Input { path: PathBuf::from("poem.txt"), from_dir: false, cannot_match: false }).collect();
+        let result = run_parallel(Arc::new(config), Arc::new(searcher_for("nobody")), inputs, &mut Closed);
+        assert_eq!(io::ErrorKind::BrokenPipe, result.unwrap_err().kind());
+    }
+
+    #[test]
+    fn run_parallel_adds_up_stats_and_prints_json() {
+        let config = Config { patterns: vec![String::from("nobody")], case_sensitive: true, json: true, ..Config::default() };
+        let inputs: Vec<_> = ["poem.txt", "Cargo.toml"].iter()
+            .map(|path| Input { path: PathBuf::from(path), from_dir: false, cannot_match: false })
+            .collect();
+
+        let mut output = Vec::new();
+        let (stats, _) = run_parallel(Arc::new(config), Arc::new(searcher_for("nobody")), inputs, &mut output).unwrap();
+        assert_eq!((2, 1, 2), (stats.searches, stats.searches_with_match, stats.matched_lines));
+
+        let output = String::from_utf8(output).unwrap();
+        let types: Vec<_> = output.lines().map(|line| &line[..16]).collect();
+        let expected = [
+            r#"{"type":"begin","#, r#"{"type":"match","#, r#"{"type":"match","#, r#"{"type":"end","d"#,
+            r#"{"type":"begin","#, r#"{"type":"end","d"#,
+        ];
+        assert_eq!(expected.to_vec(), types);
+    }
+
+    fn searcher_for(query: &str) -> Searcher {
+        searcher::SearcherBuilder::new().pattern(query).build().unwrap()
+    }
+
+    #[test]
+    fn run_with_directory_should_pass() {
+        let args = vec!(String::from("program name"), String::from("nobody"), String::from("src"));
+        let result = Config::new(args.into_iter()).unwrap();
+        assert!(run(result).is_ok());
+    }
+}
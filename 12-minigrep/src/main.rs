@@ -1,20 +1,46 @@
 use std::env;
-use std::fs;
+use std::path::Path;
 use std::process;
 
 use minigrep::{Config, run};
+use minigrep::args::ArgsError;
+use minigrep::error::MinigrepError;
+use minigrep::index::Index;
 
-// Usage: cargo run searchstring example-filename.txt
+// Usage: cargo run -- [OPTIONS] searchstring example-filename.txt
+//        cargo run -- [OPTIONS] searchstring example-directory
+//        cat example-filename.txt | cargo run -- [OPTIONS] searchstring
+//        cargo run -- index build example-directory
+// Run `cargo run -- --help` to list the options
 fn main() {
     // error message is captured in the closure, i.e. |err|
     let config = Config::new(env::args()).unwrap_or_else(|err| {
+        if err == ArgsError::Help {
+            println!("{}", err);
+            process::exit(0);
+        }
         eprintln!("Problem parsing arguments: {}", err);
-        process::exit(1);
+        process::exit(MinigrepError::Usage(err).exit_code());
     });
 
+    if let Some(dir) = &config.build_index {
+        match Index::build(Path::new(dir)) {
+            Ok(built) => println!("indexed {} files in {}, {} of them new or changed", built.files, dir, built.updated),
+            Err(e) => {
+                let e = MinigrepError::file(Path::new(dir), e);
+                eprintln!("application error: {}", e);
+                process::exit(e.exit_code());
+            }
+        }
+        return;
+    }
+
+    // Like grep: 0 when a line was selected, 1 when none was, 2 after an error
     if let Err(e) = run(config) {
-        eprintln!("application error: {}", e);
-        process::exit(1);
+        if !matches!(e, MinigrepError::NoMatch) {
+            eprintln!("application error: {}", e);
+        }
+        process::exit(e.exit_code());
     }
 
 }
@@ -0,0 +1,322 @@
+// Decides whether a line matches the patterns, according to the search options in Config.
+use crate::aho_corasick::AhoCorasick;
+use crate::casefold::FoldedQuery;
+use crate::fuzzy::{Fuzzy, FuzzyMatch};
+use crate::regex::{self, Captures, Regex, RegexBuilder};
+use crate::replace::Replacement;
+use crate::Config;
+
+#[derive(Debug, Clone)]
+enum Pattern {
+    Literal(String),
+    CaseInsensitive(FoldedQuery),
+    // Any number of literal patterns other than one
+    Multiple(AhoCorasick),
+    Regex(Regex),
+    // Parts of the line within an edit distance of any of the patterns
+    Fuzzy(Vec<Fuzzy>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Matcher {
+    pattern: Pattern,
+    // Only match whole words, a regex pattern takes care of this itself
+    word: bool,
+    // Select the lines that do not match
+    invert: bool,
+}
+
+impl Matcher {
+    pub fn new(config: &Config) -> Result<Matcher, regex::Error> {
+        let patterns = &config.patterns;
+        let pattern = if let Some(max_distance) = config.fuzzy {
+            Pattern::Fuzzy(patterns.iter().map(|pattern| Fuzzy::new(pattern, max_distance, !config.case_sensitive)).collect())
+        } else if config.regex && !patterns.is_empty() {
+            // Compile the patterns on their own first, so errors point into the right pattern
+            for pattern in patterns {
+                Regex::new(pattern)?;
+            }
+            let alternation: Vec<String> = patterns.iter().map(|pattern| format!("(?:{})", pattern)).collect();
+            let regex = RegexBuilder::new(&alternation.join("|"))
+                .case_insensitive(!config.case_sensitive)
+                .word(config.word_regexp)
+                .multi_line(config.multiline)
+                .build()?;
+            Pattern::Regex(regex)
+        } else if patterns.len() != 1 {
+            Pattern::Multiple(AhoCorasick::new(patterns, !config.case_sensitive))
+        } else if config.case_sensitive {
+            Pattern::Literal(patterns[0].clone())
+        } else {
+            Pattern::CaseInsensitive(FoldedQuery::new(&patterns[0]))
+        };
+
+        Ok(Matcher { pattern, word: config.word_regexp, invert: config.invert_match })
+    }
+
+    pub fn is_match(&self, line: &str) -> bool {
+        let matched = match &self.pattern {
+            Pattern::Literal(query) => self.find_literal(line, 0, |start| find_str(line, start, query)).is_some(),
+            Pattern::CaseInsensitive(query) => self.find_literal(line, 0, |start| query.find_at(line, start)).is_some(),
+            Pattern::Multiple(automaton) => self.find_literal(line, 0, |start| automaton.find_at(line, start)).is_some(),
+            Pattern::Regex(regex) => regex.is_match(line),
+            Pattern::Fuzzy(fuzzies) => self.find_literal(line, 0, |start| find_fuzzy(fuzzies, line, start)).is_some(),
+        };
+        matched != self.invert
+    }
+
+    // Returns the substring every matching line contains, when there is one. Lines containing it
+    // still have to be checked with is_match, e.g. for whole words.
+    pub fn literal(&self) -> Option<&str> {
+        match &self.pattern {
+            Pattern::Literal(query) if !self.invert => Some(query),
+            _ => None,
+        }
+    }
+
+    // Returns the byte ranges of all the non-overlapping matches in the line, used for highlighting.
+    // Empty matches are left out, and so is everything when the matching is inverted.
+    pub fn find_iter(&self, line: &str) -> Vec<(usize, usize)> {
+        if self.invert {
+            return Vec::new();
+        }
+        self.find_spans(line)
+    }
+
+    // Whether the lines that do not match are selected
+    pub fn is_inverted(&self) -> bool {
+        self.invert
+    }
+
+    // Like find_iter, but ignoring invert, for text that can hold several lines (see multiline)
+    pub fn find_spans(&self, text: &str) -> Vec<(usize, usize)> {
+        match &self.pattern {
+            Pattern::Literal(query) => spans(text, |start| self.find_literal(text, start, |start| find_str(text, start, query)), |span| *span),
+            Pattern::CaseInsensitive(query) => spans(text, |start| self.find_literal(text, start, |start| query.find_at(text, start)), |span| *span),
+            Pattern::Multiple(automaton) => spans(text, |start| self.find_literal(text, start, |start| automaton.find_at(text, start)), |span| *span),
+            Pattern::Regex(regex) => spans(text, |start| regex.find_at(text, start), |span| *span),
+            Pattern::Fuzzy(fuzzies) => spans(text, |start| self.find_literal(text, start, |start| find_fuzzy(fuzzies, text, start)), |span| *span),
+        }
+    }
+
+    // Returns the edit distance between the patterns and the closest part of the line,
+    // when matching approximately and the line matches
+    pub fn distance(&self, line: &str) -> Option<usize> {
+        match &self.pattern {
+            Pattern::Fuzzy(fuzzies) if !self.invert => {
+                fuzzies.iter().filter_map(|fuzzy| fuzzy.best_match(line)).map(|(_, _, distance)| distance).min()
+            }
+            _ => None,
+        }
+    }
+
+    // Like find_iter, but also returns where the capturing groups of a regex matched.
+    // Other patterns only have group 0, the whole match.
+    pub fn captures_iter(&self, line: &str) -> Vec<Captures> {
+        match &self.pattern {
+            Pattern::Regex(regex) if !self.invert => {
+                // Group 0 is always set when there is a match
+                spans(line, |start| regex.captures_at(line, start), |captures| captures[0].unwrap_or_default())
+            }
+            _ => self.find_iter(line).into_iter().map(|span| vec![Some(span)]).collect(),
+        }
+    }
+
+    // Returns the line with every match replaced
+    pub fn replace(&self, line: &str, replacement: &Replacement) -> String {
+        let mut replaced = String::with_capacity(line.len());
+        let mut written = 0;
+        for captures in self.captures_iter(line) {
+            if let Some((start, end)) = captures[0] {
+                replaced.push_str(&line[written..start]);
+                replacement.expand(line, &captures, &mut replaced);
+                written = end;
+            }
+        }
+        replaced.push_str(&line[written..]);
+        replaced
+    }
+
+    // Finds the first occurrence at or after `start` with `find_at`.
+    // For whole words, every occurrence is tried until one of them is not part of a larger word.
+    fn find_literal<F>(&self, line: &str, mut start: usize, find_at: F) -> Option<(usize, usize)>
+    where
+        F: Fn(usize) -> Option<(usize, usize)>,
+    {
+        while let Some((begin, end)) = find_at(start) {
+            if !self.word || (!ends_with_word_char(&line[..begin]) && !starts_with_word_char(&line[end..])) {
+                return Some((begin, end));
+            }
+            start = begin + line[begin..].chars().next()?.len_utf8();
+        }
+        None
+    }
+}
+
+// Finds the leftmost match of any of the patterns, the closest one if several start there
+fn find_fuzzy(fuzzies: &[Fuzzy], line: &str, start: usize) -> Option<(usize, usize)> {
+    fuzzies.iter()
+        .filter_map(|fuzzy| fuzzy.find_at(line, start))
+        .min_by_key(|(begin, _, distance)| (*begin, *distance))
+        .map(|(begin, end, _): FuzzyMatch| (begin, end))
+}
+
+fn find_str(line: &str, start: usize, query: &str) -> Option<(usize, usize)> {
+    line[start..].find(query).map(|offset| (start + offset, start + offset + query.len()))
+}
+
+// Collects the non-empty matches found by `find_at`, `span` returns the byte range of a match
+fn spans<T, F>(text: &str, find_at: F, span: fn(&T) -> (usize, usize)) -> Vec<T>
+where
+    F: Fn(usize) -> Option<T>,
+{
+    let mut spans = Vec::new();
+    let mut start = 0;
+
+    while let Some(found) = find_at(start) {
+        let (begin, end) = span(&found);
+        if begin < end {
+            spans.push(found);
+            start = end;
+        } else {
+            // Step over an empty match, otherwise we would find it again
+            match text[begin..].chars().next() {
+                Some(c) => start = begin + c.len_utf8(),
+                None => break,
+            }
+        }
+    }
+
+    spans
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn starts_with_word_char(text: &str) -> bool {
+    text.chars().next().is_some_and(is_word_char)
+}
+
+fn ends_with_word_char(text: &str) -> bool {
+    text.chars().next_back().is_some_and(is_word_char)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matcher(query: &str, configure: fn(&mut Config)) -> Matcher {
+        let mut config = Config { patterns: vec![String::from(query)], case_sensitive: true, ..Config::default() };
+        configure(&mut config);
+        Matcher::new(&config).unwrap()
+    }
+
+    #[test]
+    fn literal_word_matching() {
+        let matcher = matcher("rust", |config| config.word_regexp = true);
+        assert!(matcher.is_match("trust rust"));
+        assert!(matcher.is_match("rust-lang"));
+        assert!(!matcher.is_match("trust rusty"));
+    }
+
+    #[test]
+    fn case_insensitive_word_matching() {
+        let matcher = matcher("rust", |config| {
+            config.case_sensitive = false;
+            config.word_regexp = true;
+        });
+        assert!(matcher.is_match("Hello RUST!"));
+        assert!(!matcher.is_match("TRUST"));
+    }
+
+    #[test]
+    fn inverted_matching() {
+        let matcher = matcher("rust", |config| config.invert_match = true);
+        assert!(!matcher.is_match("trust"));
+        assert!(matcher.is_match("Rust"));
+    }
+
+    #[test]
+    fn find_iter_returns_every_match() {
+        assert_eq!(vec![(1, 3), (7, 9)], matcher("us", |_| {}).find_iter("Rust Trust"));
+        assert_eq!(vec![(5, 7)], matcher("us", |config| config.word_regexp = true).find_iter("Rust us Trust"));
+        assert_eq!(Vec::<(usize, usize)>::new(), matcher("us", |config| config.invert_match = true).find_iter("Rust"));
+    }
+
+    #[test]
+    fn find_iter_reports_original_offsets_when_ignoring_case() {
+        let matcher = matcher("straße", |config| config.case_sensitive = false);
+        // 'İ' lowercases to two characters, which shifts the lowercased line
+        assert_eq!(vec![(4, 12)], matcher.find_iter("İ: STRAẞE"));
+        assert_eq!(vec![(0, 7), (8, 15)], matcher.find_iter("STRASSE strasse"));
+    }
+
+    #[test]
+    fn case_folded_word_matching() {
+        let matcher = matcher("strasse", |config| {
+            config.case_sensitive = false;
+            config.word_regexp = true;
+        });
+        assert!(matcher.is_match("die Straße."));
+        assert!(!matcher.is_match("Hauptstraße"));
+    }
+
+    fn patterns(patterns: &[&str], configure: fn(&mut Config)) -> Matcher {
+        let mut config = Config { patterns: patterns.iter().map(|pattern| String::from(*pattern)).collect(), case_sensitive: true, ..Config::default() };
+        configure(&mut config);
+        Matcher::new(&config).unwrap()
+    }
+
+    #[test]
+    fn multiple_patterns() {
+        let matcher = patterns(&["frog", "bog", "June"], |_| {});
+        assert_eq!(vec![(5, 9), (26, 29)], matcher.find_iter("be a frog, to an admiring bog"));
+        assert!(!matcher.is_match("How dreary to be somebody!"));
+        assert!(patterns(&["FROG", "june"], |config| config.case_sensitive = false).is_match("the livelong June"));
+        assert!(!patterns(&[], |_| {}).is_match("anything"));
+    }
+
+    #[test]
+    fn multiple_regex_patterns() {
+        let matcher = patterns(&["^How", "b.g$"], |config| config.regex = true);
+        assert!(matcher.is_match("How public"));
+        assert!(matcher.is_match("an admiring bog"));
+        assert!(!matcher.is_match("To tell your name"));
+    }
+
+    #[test]
+    fn replace_matches() {
+        let replacement = Replacement::new("<$2 $1>", true);
+        let matcher = matcher(r"(\w+)@(\w+)", |config| config.regex = true);
+        assert_eq!("mail <foo ann>, <bar bob>", matcher.replace("mail ann@foo, bob@bar", &replacement));
+        assert_eq!("Trust me", matcher.replace("Trust me", &replacement));
+        assert_eq!("R<$2 $1>t", self::matcher("us", |_| {}).replace("Rust", &Replacement::new("<$2 $1>", false)));
+    }
+
+    #[test]
+    fn fuzzy_matching() {
+        let matcher = patterns(&["frog", "nobody"], |config| config.fuzzy = Some(1));
+        assert_eq!(vec![(4, 9), (18, 21)], matcher.find_iter("I'm nbody, like a fog"));
+        assert_eq!(Some(1), matcher.distance("How public, like a frg"));
+        assert_eq!(Some(0), matcher.distance("I'm nobody!"));
+        assert!(!matcher.is_match("How dreary"));
+        assert_eq!(None, self::matcher("frog", |_| {}).distance("frog"));
+    }
+
+    #[test]
+    fn find_iter_skips_empty_regex_matches() {
+        let matcher = matcher("o*", |config| config.regex = true);
+        assert_eq!(vec![(1, 3), (5, 6)], matcher.find_iter("foo bo"));
+    }
+
+    #[test]
+    fn regex_word_matching() {
+        let matcher = matcher("ru.t", |config| {
+            config.regex = true;
+            config.word_regexp = true;
+        });
+        assert!(matcher.is_match("a rust"));
+        assert!(!matcher.is_match("a trust"));
+    }
+}
@@ -0,0 +1,98 @@
+// Read-only memory maps of files, so a large file can be searched as one slice without copying it.
+//
+// There is no memory map in std, so on 64-bit Unix mmap and munmap are called directly. off_t, the
+// type of mmap's offset, is only known to be 64 bits there. Elsewhere, and for files that can't be
+// mapped (empty files, pipes, special file systems, ...), map returns None and the file is read instead.
+//
+// If another program truncates the file while it is mapped, reading the missing part kills the
+// process with SIGBUS. grep tools that use memory maps accept that risk, and --no-mmap avoids it.
+use std::fs::File;
+use std::io;
+use std::ops::Deref;
+
+pub struct Mmap {
+    ptr: *const u8,
+    len: usize,
+}
+
+#[cfg(all(unix, target_pointer_width = "64"))]
+mod sys {
+    use std::ffi::c_void;
+
+    pub const PROT_READ: i32 = 1;
+    pub const MAP_PRIVATE: i32 = 2;
+
+    extern "C" {
+        pub fn mmap(addr: *mut c_void, len: usize, prot: i32, flags: i32, fd: i32, offset: i64) -> *mut c_void;
+        pub fn munmap(addr: *mut c_void, len: usize) -> i32;
+    }
+}
+
+impl Mmap {
+    #[cfg(all(unix, target_pointer_width = "64"))]
+    pub fn map(file: &File) -> io::Result<Option<Mmap>> {
+        use std::os::unix::io::AsRawFd;
+
+        let metadata = file.metadata()?;
+        if !metadata.is_file() || metadata.len() == 0 {
+            return Ok(None);
+        }
+        let len = match usize::try_from(metadata.len()) {
+            Ok(len) => len,
+            Err(_) => return Ok(None),
+        };
+
+        // SAFETY: a new private read-only mapping doesn't alias any memory Rust knows about
+        let ptr = unsafe { sys::mmap(std::ptr::null_mut(), len, sys::PROT_READ, sys::MAP_PRIVATE, file.as_raw_fd(), 0) };
+        // MAP_FAILED is (void *) -1, e.g. with ENODEV on a file system that can't map files
+        if ptr as isize == -1 {
+            return Ok(None);
+        }
+        Ok(Some(Mmap { ptr: ptr as *const u8, len }))
+    }
+
+    #[cfg(not(all(unix, target_pointer_width = "64")))]
+    pub fn map(_file: &File) -> io::Result<Option<Mmap>> {
+        Ok(None)
+    }
+}
+
+impl Deref for Mmap {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // SAFETY: the mapping stays valid until drop
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        #[cfg(all(unix, target_pointer_width = "64"))]
+        // SAFETY: the mapping was created by map and isn't used after this
+        unsafe {
+            sys::munmap(self.ptr as *mut _, self.len);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    #[cfg(all(unix, target_pointer_width = "64"))]
+    fn maps_file_contents() {
+        let map = Mmap::map(&File::open("poem.txt").unwrap()).unwrap().unwrap();
+        assert_eq!(fs::read("poem.txt").unwrap(), &map[..]);
+    }
+
+    #[test]
+    fn empty_files_are_not_mapped() {
+        let path = std::env::temp_dir().join(format!("minigrep-mmap-{}.txt", std::process::id()));
+        fs::write(&path, "").unwrap();
+        assert!(Mmap::map(&File::open(&path).unwrap()).unwrap().is_none());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,150 @@
+// Search for --multiline, where a match can span several lines.
+//
+// The patterns are matched against the whole input instead of one line at a time, so a regex like
+// `fn \w+\(.*\)\s*\{\n\s+let` can match a function signature together with the start of its body.
+// Every line a match touches is a matching line, so the results cover the full range of lines
+// of each match. `^` and `$` match at the start and end of every line (see regex).
+use std::io;
+
+use crate::context::ContextLine;
+use crate::matcher::Matcher;
+use crate::sink::Stats;
+
+// Calls `output` for every line of every match and for the context lines, in order.
+// With `max_count`, only the lines of the first `max_count` matches are selected.
+pub fn search_multiline<F>(
+    matcher: &Matcher,
+    contents: &str,
+    before: usize,
+    after: usize,
+    max_count: Option<usize>,
+    mut output: F,
+) -> io::Result<Stats>
+where
+    F: FnMut(&ContextLine) -> io::Result<()>,
+{
+    let starts = line_starts(contents);
+    let mut selected = vec![false; starts.len()];
+
+    // Empty matches are left out by find_spans, so the byte before the end is part of the match
+    let matches = matcher.find_spans(contents);
+    let limit = if matcher.is_inverted() { usize::MAX } else { max_count.unwrap_or(usize::MAX) };
+    for (start, end) in matches.into_iter().take(limit) {
+        selected[line_index(&starts, start)..=line_index(&starts, end - 1)].fill(true);
+    }
+    if matcher.is_inverted() {
+        let mut left = max_count.unwrap_or(usize::MAX);
+        for is_selected in &mut selected {
+            *is_selected = !*is_selected && left > 0;
+            left -= usize::from(*is_selected);
+        }
+    }
+
+    let mut stats = Stats { searches: 1, bytes_searched: contents.len(), ..Stats::default() };
+    let line = |index: usize, is_match: bool| {
+        let end = starts.get(index + 1).copied().unwrap_or(contents.len());
+        let text = &contents[starts[index]..end];
+        let text = text.strip_suffix('\n').unwrap_or(text);
+        let text = text.strip_suffix('\r').unwrap_or(text);
+        ContextLine { number: index + 1, offset: starts[index], text, is_match }
+    };
+    // Index of the first line that wasn't output yet
+    let mut next = 0;
+    let mut after_left = 0;
+
+    for (index, is_selected) in selected.into_iter().enumerate() {
+        if is_selected {
+            stats.matched_lines += 1;
+            for context in index.saturating_sub(before).max(next)..index {
+                output(&line(context, false))?;
+            }
+            output(&line(index, true))?;
+            after_left = after;
+        } else if after_left > 0 {
+            after_left -= 1;
+            output(&line(index, false))?;
+        } else {
+            continue;
+        }
+        next = index + 1;
+    }
+
+    stats.searches_with_match = (stats.matched_lines > 0) as usize;
+    Ok(stats)
+}
+
+// Returns the byte offset of the start of every line.
+// Like str::lines, there is no empty line after a final line ending.
+fn line_starts(contents: &str) -> Vec<usize> {
+    let mut starts = Vec::new();
+    if !contents.is_empty() {
+        starts.push(0);
+    }
+    starts.extend(contents.match_indices('\n').map(|(index, _)| index + 1).filter(|start| *start < contents.len()));
+    starts
+}
+
+// Returns the index of the line containing the byte at `offset`
+fn line_index(starts: &[usize], offset: usize) -> usize {
+    starts.partition_point(|start| *start <= offset) - 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+
+    const CONTENTS: &str = "\
+fn main() {
+    let x = 1;
+}
+
+fn helper()
+{
+    let y = 2;
+}
+";
+
+    fn collect(config: Config, before: usize, after: usize, max_count: Option<usize>) -> Vec<(usize, bool)> {
+        let matcher = Matcher::new(&Config { case_sensitive: true, multiline: true, ..config }).unwrap();
+        let mut lines = Vec::new();
+        search_multiline(&matcher, CONTENTS, before, after, max_count, |line| {
+            lines.push((line.number, line.is_match));
+            Ok(())
+        }).unwrap();
+        lines
+    }
+
+    fn regex(pattern: &str) -> Config {
+        Config { patterns: vec![String::from(pattern)], regex: true, ..Config::default() }
+    }
+
+    #[test]
+    fn matches_span_lines() {
+        let lines = collect(regex(r"fn \w+\(\)\s*\{\n\s+let"), 0, 0, None);
+        assert_eq!(vec![(1, true), (2, true), (5, true), (6, true), (7, true)], lines);
+    }
+
+    #[test]
+    fn max_count_and_context() {
+        let lines = collect(regex(r"fn \w+\(\)\s*\{\n\s+let"), 0, 1, Some(1));
+        assert_eq!(vec![(1, true), (2, true), (3, false)], lines);
+        let lines = collect(regex(r"^\}$"), 1, 0, None);
+        assert_eq!(vec![(2, false), (3, true), (7, false), (8, true)], lines);
+    }
+
+    #[test]
+    fn inverted_matches_select_the_other_lines() {
+        let config = Config { invert_match: true, ..regex(r"\{\n[^}]*\}") };
+        assert_eq!(vec![(4, true), (5, true)], collect(config, 0, 0, None));
+    }
+
+    #[test]
+    fn line_starts_are_like_lines() {
+        assert_eq!(Vec::<usize>::new(), line_starts(""));
+        assert_eq!(vec![0, 2, 3], line_starts("a\n\nb\n"));
+        assert_eq!(0, line_index(&[0, 2, 3], 1));
+        assert_eq!(1, line_index(&[0, 2, 3], 2));
+        assert_eq!(2, line_index(&[0, 2, 3], 4));
+    }
+}
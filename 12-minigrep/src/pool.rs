@@ -0,0 +1,127 @@
+// The thread pool from the multi-threaded web server, used to search several files at once.
+// Workers don't log anything here, since stdout carries the search results.
+// A job that panics doesn't take its worker down, so the other jobs still run.
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread;
+
+pub struct ThreadPool {
+    workers: Vec<Worker>,
+    sender: mpsc::Sender<Message>,
+}
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+enum Message {
+    NewJob(Job),
+    Terminate,
+}
+
+impl ThreadPool {
+    /// Create a new ThreadPool.
+    ///
+    /// The size is the number of threads in the pool.
+    ///
+    /// # Panics
+    ///
+    /// The `new` function will panic if the size is zero.
+    pub fn new(size: usize) -> ThreadPool {
+        assert!(size > 0);
+
+        let (sender, receiver) = mpsc::channel();
+        // The Arc type will let multiple workers own the receiver,
+        // and Mutex will ensure that only one worker gets a job from the receiver at a time.
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        let mut workers = Vec::with_capacity(size);
+
+        for _ in 0..size {
+            workers.push(Worker::new(Arc::clone(&receiver)));
+        }
+
+        ThreadPool { workers, sender }
+    }
+
+    pub fn execute<F>(&self, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let job = Box::new(f);
+        self.sender.send(Message::NewJob(job)).unwrap();
+    }
+}
+
+impl Drop for ThreadPool {
+    // Jobs that were already queued still run before the workers terminate
+    fn drop(&mut self) {
+        for _ in &self.workers {
+            self.sender.send(Message::Terminate).unwrap();
+        }
+
+        for worker in &mut self.workers {
+            if let Some(thread) = worker.thread.take() {
+                thread.join().unwrap();
+            }
+        }
+    }
+}
+
+struct Worker {
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Worker {
+    fn new(receiver: Arc<Mutex<mpsc::Receiver<Message>>>) -> Worker {
+        let thread = thread::spawn(move || loop {
+            let message = receiver.lock().unwrap().recv().unwrap();
+
+            match message {
+                Message::NewJob(job) => {
+                    let _ = panic::catch_unwind(AssertUnwindSafe(job));
+                }
+                Message::Terminate => break,
+            }
+        });
+
+        Worker { thread: Some(thread) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_every_job_before_shutting_down() {
+        let (sender, receiver) = mpsc::channel();
+        let pool = ThreadPool::new(3);
+        for i in 0..10 {
+            let sender = sender.clone();
+            pool.execute(move || sender.send(i).unwrap());
+        }
+        drop(pool);
+        drop(sender);
+
+        let mut results: Vec<i32> = receiver.iter().collect();
+        results.sort();
+        assert_eq!((0..10).collect::<Vec<_>>(), results);
+    }
+
+    #[test]
+    fn survives_jobs_that_panic() {
+        let (sender, receiver) = mpsc::channel();
+        let pool = ThreadPool::new(1);
+        pool.execute(|| panic!("job failed"));
+        pool.execute(move || sender.send(1).unwrap());
+        drop(pool);
+        assert_eq!(vec![1], receiver.iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    #[should_panic]
+    fn new_with_zero_threads_should_panic() {
+        ThreadPool::new(0);
+    }
+}
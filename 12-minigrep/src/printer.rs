@@ -0,0 +1,379 @@
+// Writes search results the way grep does: matching lines use ':' after the path and line number,
+// context lines use '-', and groups of lines that are not adjacent are separated by "--".
+// Instead of the lines, it can print only the matches (-o), counts (-c) or file names (-l and -L).
+//
+// With --column and -b, the prefix also holds the column of the first match on the line and the
+// byte offset of the line in the (decoded) input, or with -o those of each match. Columns count
+// bytes from 1, offsets count bytes from 0. --vimgrep prints path:line:column:line for every match,
+// the format that vim's :grep and most editors read.
+use std::borrow::Cow;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::color::{self, paint, ColorChoice};
+use crate::context::ContextLine;
+use crate::matcher::Matcher;
+use crate::regex::Captures;
+use crate::replace::Replacement;
+use crate::sink::{Sink, Stats};
+use crate::Config;
+
+// What is printed for each input
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Output {
+    Lines,
+    // Every match on a line of its own
+    OnlyMatching,
+    // The number of matching lines
+    Count,
+    // The path of inputs with a match, or without one
+    FilesWithMatches,
+    FilesWithoutMatch,
+}
+
+pub struct Printer<'m, W: Write> {
+    out: W,
+    // Used to find the parts of a line to highlight
+    matcher: &'m Matcher,
+    color: bool,
+    with_filename: bool,
+    line_number: bool,
+    column: bool,
+    byte_offset: bool,
+    // Print a matching line once for every match on it
+    vimgrep: bool,
+    output: Output,
+    // Print matching lines with their matches replaced
+    replacement: Option<Replacement>,
+    separate_groups: bool,
+    // The number of the last line printed from the current input
+    last_number: Option<usize>,
+    printed_line: bool,
+}
+
+impl<'m, W: Write> Printer<'m, W> {
+    // Config::color should already be resolved, Auto is treated as Never
+    pub fn new(config: &Config, matcher: &'m Matcher, out: W) -> Printer<'m, W> {
+        Printer {
+            out,
+            matcher,
+            color: config.color == ColorChoice::Always,
+            with_filename: config.with_filename == Some(true) || config.vimgrep,
+            line_number: config.line_number == Some(true) || config.vimgrep,
+            column: config.column || config.vimgrep,
+            byte_offset: config.byte_offset,
+            vimgrep: config.vimgrep,
+            output: if config.files_with_matches {
+                Output::FilesWithMatches
+            } else if config.files_without_match {
+                Output::FilesWithoutMatch
+            } else if config.count {
+                Output::Count
+            } else if config.only_matching {
+                Output::OnlyMatching
+            } else {
+                Output::Lines
+            },
+            replacement: config.replace.as_ref().map(|text| Replacement::new(text, config.regex)),
+            separate_groups: config.before_context > 0 || config.after_context > 0,
+            last_number: None,
+            printed_line: false,
+        }
+    }
+
+    fn print_path(&mut self, path: &Path, separator: &str) -> io::Result<()> {
+        let path = paint(&path.display().to_string(), color::PATH, self.color);
+        write!(self.out, "{}{}", path, paint(separator, color::SEPARATOR, self.color))
+    }
+
+    // The path, line number, column and byte offset in front of a line, as far as they are printed.
+    // `start` is where the match printed starts, None when printing the whole line.
+    fn print_prefix(&mut self, path: &Path, line: &ContextLine, separator: &str, start: Option<usize>) -> io::Result<()> {
+        if self.with_filename {
+            self.print_path(path, separator)?;
+        }
+        if self.line_number {
+            let number = paint(&line.number.to_string(), color::LINE_NUMBER, self.color);
+            write!(self.out, "{}{}", number, paint(separator, color::SEPARATOR, self.color))?;
+        }
+        // Context lines have no match to point at. Lines selected by -v have none either, like rg
+        // their column is 1, so --vimgrep output keeps its format.
+        if self.column && line.is_match {
+            let column = start.or_else(|| self.matcher.find_iter(line.text).first().map(|(start, _)| *start)).unwrap_or(0) + 1;
+            let column = paint(&column.to_string(), color::LINE_NUMBER, self.color);
+            write!(self.out, "{}{}", column, paint(separator, color::SEPARATOR, self.color))?;
+        }
+        if self.byte_offset {
+            let offset = paint(&(line.offset + start.unwrap_or(0)).to_string(), color::LINE_NUMBER, self.color);
+            write!(self.out, "{}{}", offset, paint(separator, color::SEPARATOR, self.color))?;
+        }
+        Ok(())
+    }
+
+    // The replacement of a match when replacing, otherwise the match itself
+    fn match_text<'t>(&self, text: &'t str, captures: &Captures) -> Cow<'t, str> {
+        let (start, end) = captures[0].unwrap_or_default();
+        match &self.replacement {
+            Some(replacement) => {
+                let mut replaced = String::new();
+                replacement.expand(text, captures, &mut replaced);
+                Cow::Owned(replaced)
+            }
+            None => Cow::Borrowed(&text[start..end]),
+        }
+    }
+
+    fn print_matches(&mut self, path: &Path, line: &ContextLine) -> io::Result<()> {
+        for captures in self.matcher.captures_iter(line.text) {
+            if captures[0].is_none() {
+                continue;
+            }
+            self.print_prefix(path, line, ":", captures[0].map(|(start, _)| start))?;
+            let text = self.match_text(line.text, &captures);
+            writeln!(self.out, "{}", paint(&text, color::MATCH, self.color))?;
+        }
+        Ok(())
+    }
+
+    // The text of a line, with its matches highlighted or replaced
+    fn print_text(&mut self, line: &ContextLine) -> io::Result<()> {
+        if !line.is_match || (!self.color && self.replacement.is_none()) {
+            return writeln!(self.out, "{}", line.text);
+        }
+
+        // Matches are highlighted, or replaced (and then the replacements are highlighted)
+        let mut written = 0;
+        for captures in self.matcher.captures_iter(line.text) {
+            let (start, end) = match captures[0] {
+                Some(span) => span,
+                None => continue,
+            };
+            let text = self.match_text(line.text, &captures);
+            write!(self.out, "{}{}", &line.text[written..start], paint(&text, color::MATCH, self.color))?;
+            written = end;
+        }
+        writeln!(self.out, "{}", &line.text[written..])
+    }
+}
+
+impl<W: Write> Sink for Printer<'_, W> {
+    fn begin(&mut self, _path: &Path) -> io::Result<()> {
+        // Line numbers start over, so the next line is never adjacent to the last one printed
+        self.last_number = None;
+        Ok(())
+    }
+
+    fn line(&mut self, path: &Path, line: &ContextLine) -> io::Result<()> {
+        match self.output {
+            Output::Lines => {}
+            Output::OnlyMatching if line.is_match => return self.print_matches(path, line),
+            _ => return Ok(()),
+        }
+
+        let adjacent = self.last_number.is_some_and(|number| number + 1 == line.number);
+        if self.separate_groups && self.printed_line && !adjacent {
+            writeln!(self.out, "{}", paint("--", color::SEPARATOR, self.color))?;
+        }
+        self.last_number = Some(line.number);
+        self.printed_line = true;
+
+        let separator = if line.is_match { ":" } else { "-" };
+        let spans = if self.vimgrep && line.is_match { self.matcher.find_iter(line.text) } else { Vec::new() };
+        if spans.is_empty() {
+            self.print_prefix(path, line, separator, None)?;
+            return self.print_text(line);
+        }
+        for (start, _) in spans {
+            self.print_prefix(path, line, separator, Some(start))?;
+            self.print_text(line)?;
+        }
+        Ok(())
+    }
+
+    fn binary_match(&mut self, path: &Path) -> io::Result<()> {
+        match self.output {
+            Output::Lines | Output::OnlyMatching => writeln!(self.out, "Binary file {} matches", path.display()),
+            _ => Ok(()),
+        }
+    }
+
+    fn end(&mut self, path: &Path, stats: &Stats) -> io::Result<()> {
+        let matched = stats.matched_lines > 0;
+        match self.output {
+            Output::Count => {
+                if self.with_filename {
+                    self.print_path(path, ":")?;
+                }
+                writeln!(self.out, "{}", stats.matched_lines)
+            }
+            Output::FilesWithMatches | Output::FilesWithoutMatch if matched == (self.output == Output::FilesWithMatches) => {
+                writeln!(self.out, "{}", paint(&path.display().to_string(), color::PATH, self.color))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(number: usize, text: &str, is_match: bool) -> ContextLine<'_> {
+        ContextLine { number, offset: 0, text, is_match }
+    }
+
+    fn matcher() -> Matcher {
+        let config = Config { patterns: vec![String::from("us")], case_sensitive: true, ..Config::default() };
+        Matcher::new(&config).unwrap()
+    }
+
+    fn print(config: &Config, lines: &[ContextLine]) -> String {
+        let matcher = matcher();
+        let mut out = Vec::new();
+        let mut printer = Printer::new(config, &matcher, &mut out);
+        let path = Path::new("poem.txt");
+        printer.begin(path).unwrap();
+        for line in lines {
+            printer.line(path, line).unwrap();
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn prints_plain_lines() {
+        let output = print(&Config::default(), &[line(1, "Rust:", true), line(4, "Trust me.", true)]);
+        assert_eq!("Rust:\nTrust me.\n", output);
+    }
+
+    #[test]
+    fn prints_path_and_line_numbers() {
+        let config = Config { line_number: Some(true), with_filename: Some(true), ..Config::default() };
+        assert_eq!("poem.txt:2:nobody\n", print(&config, &[line(2, "nobody", true)]));
+    }
+
+    #[test]
+    fn prints_columns_and_byte_offsets() {
+        let lines = [ContextLine { number: 3, offset: 40, text: "Trust us", is_match: true }, ContextLine { number: 4, offset: 49, text: "Rust", is_match: false }];
+        let config = Config { line_number: Some(true), column: true, byte_offset: true, ..Config::default() };
+        assert_eq!("3:3:40:Trust us\n4-49-Rust\n", print(&config, &lines));
+        let config = Config { only_matching: true, column: true, byte_offset: true, ..Config::default() };
+        assert_eq!("3:42:us\n7:46:us\n", print(&config, &lines[..1]));
+    }
+
+    #[test]
+    fn inverted_matches_start_at_column_1() {
+        let config = Config { patterns: vec![String::from("us")], invert_match: true, ..Config::default() };
+        let matcher = Matcher::new(&config).unwrap();
+        let mut out = Vec::new();
+        let mut printer = Printer::new(&Config { column: true, ..Config::default() }, &matcher, &mut out);
+        printer.line(Path::new("poem.txt"), &line(2, "abc", true)).unwrap();
+        printer.line(Path::new("poem.txt"), &line(3, "Trust us", false)).unwrap();
+        assert_eq!("1:abc\nTrust us\n", String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn prints_a_line_for_every_match_with_vimgrep() {
+        let config = Config { vimgrep: true, ..Config::default() };
+        let lines = [line(3, "Trust us", true), line(4, "Rust", false)];
+        assert_eq!("poem.txt:3:3:Trust us\npoem.txt:3:7:Trust us\npoem.txt-4-Rust\n", print(&config, &lines));
+    }
+
+    #[test]
+    fn separates_groups_of_context() {
+        let config = Config { line_number: Some(true), after_context: 1, ..Config::default() };
+        let lines = [line(1, "a", true), line(2, "b", false), line(5, "e", true), line(6, "f", true)];
+        assert_eq!("1:a\n2-b\n--\n5:e\n6:f\n", print(&config, &lines));
+    }
+
+    #[test]
+    fn separates_groups_across_files() {
+        let config = Config { line_number: Some(true), with_filename: Some(true), before_context: 1, ..Config::default() };
+        let matcher = matcher();
+        let mut out = Vec::new();
+        let mut printer = Printer::new(&config, &matcher, &mut out);
+        printer.begin(Path::new("a")).unwrap();
+        printer.line(Path::new("a"), &line(1, "x", true)).unwrap();
+        printer.begin(Path::new("b")).unwrap();
+        printer.line(Path::new("b"), &line(2, "y", true)).unwrap();
+        assert_eq!("a:1:x\n--\nb:2:y\n", String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn prints_counts() {
+        let config = Config { count: true, with_filename: Some(true), ..Config::default() };
+        let matcher = matcher();
+        let mut out = Vec::new();
+        let mut printer = Printer::new(&config, &matcher, &mut out);
+        let path = Path::new("poem.txt");
+        printer.begin(path).unwrap();
+        printer.line(path, &line(2, "nobody", true)).unwrap();
+        printer.end(path, &Stats { matched_lines: 1, ..Stats::default() }).unwrap();
+        assert_eq!("poem.txt:1\n", String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn prints_only_matches() {
+        let config = Config { only_matching: true, line_number: Some(true), after_context: 1, ..Config::default() };
+        let lines = [line(4, "Trust us", true), line(5, "Rust", false)];
+        assert_eq!("4:us\n4:us\n", print(&config, &lines));
+    }
+
+    #[test]
+    fn prints_files_with_and_without_matches() {
+        for (config, expected) in [
+            (Config { files_with_matches: true, ..Config::default() }, "a\n"),
+            (Config { files_without_match: true, ..Config::default() }, "b\n"),
+        ] {
+            let matcher = matcher();
+            let mut out = Vec::new();
+            let mut printer = Printer::new(&config, &matcher, &mut out);
+            for (path, matched_lines) in [("a", 2), ("b", 0)] {
+                printer.begin(Path::new(path)).unwrap();
+                if matched_lines > 0 {
+                    printer.line(Path::new(path), &line(1, "us", true)).unwrap();
+                }
+                printer.end(Path::new(path), &Stats { matched_lines, ..Stats::default() }).unwrap();
+            }
+            assert_eq!(expected, String::from_utf8(out).unwrap());
+        }
+    }
+
+    #[test]
+    fn prints_binary_matches() {
+        for (config, expected) in [
+            (Config::default(), "Binary file a.out matches\n"),
+            (Config { count: true, ..Config::default() }, "1\n"),
+        ] {
+            let matcher = matcher();
+            let mut out = Vec::new();
+            let mut printer = Printer::new(&config, &matcher, &mut out);
+            let path = Path::new("a.out");
+            printer.begin(path).unwrap();
+            printer.binary_match(path).unwrap();
+            printer.end(path, &Stats { matched_lines: 1, ..Stats::default() }).unwrap();
+            assert_eq!(expected, String::from_utf8(out).unwrap());
+        }
+    }
+
+    #[test]
+    fn highlights_matches_when_colored() {
+        let config = Config { color: ColorChoice::Always, line_number: Some(true), ..Config::default() };
+        let lines = [line(4, "Trust us", true), line(5, "Rust", false)];
+        let expected = "\x1b[32m4\x1b[0m\x1b[36m:\x1b[0mTr\x1b[1;31mus\x1b[0mt \x1b[1;31mus\x1b[0m\n\
+                        \x1b[32m5\x1b[0m\x1b[36m-\x1b[0mRust\n";
+        assert_eq!(expected, print(&config, &lines));
+    }
+
+    #[test]
+    fn prints_replaced_lines() {
+        let config = Config { replace: Some(String::from("[$0]")), after_context: 1, ..Config::default() };
+        let lines = [line(4, "Trust us", true), line(5, "Rust", false)];
+        assert_eq!("Tr[$0]t [$0]\nRust\n", print(&config, &lines));
+    }
+
+    #[test]
+    fn auto_color_is_not_colored() {
+        let config = Config { color: ColorChoice::Auto, ..Config::default() };
+        assert_eq!("Trust us\n", print(&config, &[line(4, "Trust us", true)]));
+    }
+}
@@ -0,0 +1,861 @@
+// A small regular expression engine used by `--regex` mode.
+//
+// The pattern is parsed into a syntax tree, compiled into a list of instructions and
+// executed with a Pike VM: every possible path through the pattern is followed in lock step,
+// one input character at a time. Unlike a backtracking engine this never takes exponential time,
+// no matter how pathological the pattern is.
+//
+// Supported syntax:
+// - literals and `.` (any character except a newline)
+// - character classes `[abc]`, `[^a-z]`, `[[:alpha:]]` and `\d \w \s \D \W \S`
+// - anchors `^` and `$`, word boundaries `\b` and `\B`. In multi-line mode, `^` and `$` also match at
+//   the start and end of every line
+// - alternation `a|b`, capturing groups `(..)` and non-capturing groups `(?:..)`
+// - repetition `* + ? {n} {n,} {n,m}`, each optionally followed by `?` to make it lazy
+use std::error::Error as StdError;
+use std::fmt;
+
+use crate::casefold::simple_fold;
+
+// Large counted repetitions are expanded into copies of their body, so keep them bounded.
+const MAX_REPEAT: u32 = 1000;
+// Nested repetitions multiply, so the compiled program is bounded too, in instructions
+const MAX_PROGRAM_SIZE: usize = 100_000;
+
+#[derive(Debug, PartialEq)]
+pub struct Error {
+    pub message: String,
+    pub position: usize,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid regex at position {}: {}", self.position, self.message)
+    }
+}
+
+impl StdError for Error {}
+
+// The byte range of every capturing group, group 0 is the whole match.
+// Groups that did not take part in the match are None.
+pub type Captures = Vec<Option<(usize, usize)>>;
+
+#[derive(Debug, Clone)]
+pub struct Regex {
+    program: Vec<Inst>,
+    slots: usize,
+    case_insensitive: bool,
+}
+
+pub struct RegexBuilder {
+    pattern: String,
+    case_insensitive: bool,
+    word: bool,
+    multi_line: bool,
+}
+
+impl RegexBuilder {
+    pub fn new(pattern: &str) -> RegexBuilder {
+        RegexBuilder { pattern: String::from(pattern), case_insensitive: false, word: false, multi_line: false }
+    }
+
+    pub fn case_insensitive(mut self, yes: bool) -> RegexBuilder {
+        self.case_insensitive = yes;
+        self
+    }
+
+    // Only match whole words, like grep -w: a match must not be preceded or followed by a word character
+    pub fn word(mut self, yes: bool) -> RegexBuilder {
+        self.word = yes;
+        self
+    }
+
+    // Make `^` and `$` match at line boundaries, for text holding several lines
+    pub fn multi_line(mut self, yes: bool) -> RegexBuilder {
+        self.multi_line = yes;
+        self
+    }
+
+    pub fn build(self) -> Result<Regex, Error> {
+        let mut parser = Parser { chars: self.pattern.chars().collect(), pos: 0, groups: 0, multi_line: self.multi_line };
+        let node = parser.parse()?;
+
+        let mut program = Vec::new();
+        // Group 0 is the whole match
+        program.push(Inst::Save(0));
+        if self.word {
+            program.push(Inst::Assert(Assertion::NoWordBefore));
+        }
+        compile(&node, &mut program)
+            .map_err(|_| Error { message: String::from("regex too large once its repetitions are expanded"), position: 0 })?;
+        if self.word {
+            program.push(Inst::Assert(Assertion::NoWordAfter));
+        }
+        program.push(Inst::Save(1));
+        program.push(Inst::Match);
+
+        Ok(Regex { program, slots: (parser.groups + 1) * 2, case_insensitive: self.case_insensitive })
+    }
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Regex, Error> {
+        RegexBuilder::new(pattern).build()
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.find(text).is_some()
+    }
+
+    // Returns the byte range of the leftmost match
+    pub fn find(&self, text: &str) -> Option<(usize, usize)> {
+        self.find_at(text, 0)
+    }
+
+    // Like find, but the search starts at byte offset `start`.
+    // Anchors and word boundaries still look at the text before `start`.
+    pub fn find_at(&self, text: &str, start: usize) -> Option<(usize, usize)> {
+        let slots = self.exec(text, start)?;
+        Some((slots[0]?, slots[1]?))
+    }
+
+    // Like find_at, but also returns where each capturing group matched
+    pub fn captures_at(&self, text: &str, start: usize) -> Option<Captures> {
+        let slots = self.exec(text, start)?;
+        Some(slots.chunks(2).map(|slot| Some((slot[0]?, slot[1]?))).collect())
+    }
+
+    fn exec(&self, text: &str, start: usize) -> Option<Vec<Option<usize>>> {
+        let mut current = Threads::new(self.program.len());
+        let mut next = Threads::new(self.program.len());
+        let mut matched = None;
+        let mut at = start;
+
+        loop {
+            // Start a new attempt at this position, with a lower priority than the attempts
+            // that started earlier. Once something matched, we only let the earlier attempts finish.
+            if matched.is_none() {
+                self.add_thread(&mut current, 0, at, vec![None; self.slots], text);
+            }
+            if current.list.is_empty() && matched.is_some() {
+                break;
+            }
+
+            let c = text[at..].chars().next();
+            let next_at = at + c.map_or(0, |c| c.len_utf8());
+
+            for thread in current.list.drain(..) {
+                let step = match &self.program[thread.pc] {
+                    Inst::Match => {
+                        matched = Some(thread.slots);
+                        // Every remaining thread has a lower priority than this one
+                        break;
+                    }
+                    Inst::Char(expected) => c.is_some_and(|c| self.char_eq(*expected, c)),
+                    Inst::Any => c.is_some_and(|c| c != '\n'),
+                    Inst::Class(class) => c.is_some_and(|c| self.class_contains(class, c)),
+                    _ => false,
+                };
+                if step {
+                    self.add_thread(&mut next, thread.pc + 1, next_at, thread.slots, text);
+                }
+            }
+
+            if c.is_none() {
+                break;
+            }
+            at = next_at;
+            std::mem::swap(&mut current, &mut next);
+            next.clear();
+        }
+
+        matched
+    }
+
+    // Follows the instructions that do not consume input, so that `threads` only
+    // contains threads waiting on a character. The paths are followed depth first with a stack
+    // instead of recursion, since a program can chain many thousands of these instructions.
+    fn add_thread(&self, threads: &mut Threads, pc: usize, at: usize, slots: Vec<Option<usize>>, text: &str) {
+        let mut stack = vec![(pc, slots)];
+        while let Some((pc, mut slots)) = stack.pop() {
+            if threads.seen[pc] {
+                continue;
+            }
+            threads.seen[pc] = true;
+
+            match &self.program[pc] {
+                Inst::Jump(target) => stack.push((*target, slots)),
+                // The second branch is pushed first, so the first one is followed first
+                Inst::Split(first, second) => {
+                    stack.push((*second, slots.clone()));
+                    stack.push((*first, slots));
+                }
+                Inst::Save(slot) => {
+                    slots[*slot] = Some(at);
+                    stack.push((pc + 1, slots));
+                }
+                Inst::Assert(assertion) => {
+                    if assertion.holds(text, at) {
+                        stack.push((pc + 1, slots));
+                    }
+                }
+                _ => threads.list.push(Thread { pc, slots }),
+            }
+        }
+    }
+
+    fn char_eq(&self, expected: char, c: char) -> bool {
+        expected == c || (self.case_insensitive && simple_fold(expected) == simple_fold(c))
+    }
+
+    fn class_contains(&self, class: &Class, c: char) -> bool {
+        if !self.case_insensitive {
+            return class.contains(c);
+        }
+        let matched = class.items_contain(c)
+            || class.items_contain(simple_fold(c))
+            || c.to_uppercase().all(|upper| class.items_contain(upper));
+        matched != class.negated
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+struct Thread {
+    pc: usize,
+    slots: Vec<Option<usize>>,
+}
+
+struct Threads {
+    list: Vec<Thread>,
+    seen: Vec<bool>,
+}
+
+impl Threads {
+    fn new(size: usize) -> Threads {
+        Threads { list: Vec::new(), seen: vec![false; size] }
+    }
+
+    fn clear(&mut self) {
+        self.list.clear();
+        self.seen.iter_mut().for_each(|seen| *seen = false);
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Inst {
+    Char(char),
+    Any,
+    Class(Class),
+    Assert(Assertion),
+    // Try the first branch, then the second one with a lower priority
+    Split(usize, usize),
+    Jump(usize),
+    Save(usize),
+    Match,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Assertion {
+    Start,
+    End,
+    LineStart,
+    LineEnd,
+    WordBoundary,
+    NotWordBoundary,
+    NoWordBefore,
+    NoWordAfter,
+}
+
+impl Assertion {
+    fn holds(&self, text: &str, at: usize) -> bool {
+        match self {
+            Assertion::Start => at == 0,
+            Assertion::End => at == text.len(),
+            Assertion::LineStart => at == 0 || text[..at].ends_with('\n'),
+            Assertion::LineEnd => at == text.len() || text[at..].starts_with('\n') || text[at..].starts_with("\r\n"),
+            Assertion::WordBoundary | Assertion::NotWordBoundary => {
+                let before = text[..at].chars().next_back().is_some_and(is_word_char);
+                let after = text[at..].chars().next().is_some_and(is_word_char);
+                (before != after) == (*self == Assertion::WordBoundary)
+            }
+            Assertion::NoWordBefore => !text[..at].chars().next_back().is_some_and(is_word_char),
+            Assertion::NoWordAfter => !text[at..].chars().next().is_some_and(is_word_char),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Perl {
+    Digit,
+    Word,
+    Space,
+}
+
+impl Perl {
+    fn contains(&self, c: char) -> bool {
+        match self {
+            Perl::Digit => c.is_ascii_digit(),
+            Perl::Word => is_word_char(c),
+            Perl::Space => c.is_whitespace(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum ClassItem {
+    Range(char, char),
+    // A perl class such as \d, or its negation such as \D
+    Perl(Perl, bool),
+    Posix(fn(char) -> bool),
+}
+
+#[derive(Debug, Clone)]
+struct Class {
+    items: Vec<ClassItem>,
+    negated: bool,
+}
+
+impl Class {
+    fn contains(&self, c: char) -> bool {
+        self.items_contain(c) != self.negated
+    }
+
+    fn items_contain(&self, c: char) -> bool {
+        self.items.iter().any(|item| match item {
+            ClassItem::Range(low, high) => *low <= c && c <= *high,
+            ClassItem::Perl(perl, negated) => perl.contains(c) != *negated,
+            ClassItem::Posix(contains) => contains(c),
+        })
+    }
+}
+
+#[derive(Debug)]
+enum Node {
+    Empty,
+    Literal(char),
+    Any,
+    Class(Class),
+    Assert(Assertion),
+    Group(Box<Node>, Option<usize>),
+    Concat(Vec<Node>),
+    Alternate(Vec<Node>),
+    Repeat { node: Box<Node>, min: u32, max: Option<u32>, greedy: bool },
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    groups: usize,
+    multi_line: bool,
+}
+
+impl Parser {
+    fn parse(&mut self) -> Result<Node, Error> {
+        let node = self.parse_alternation()?;
+        match self.peek() {
+            None => Ok(node),
+            Some(_) => Err(self.error("unmatched ')'")),
+        }
+    }
+
+    fn error(&self, message: &str) -> Error {
+        Error { message: String::from(message), position: self.pos }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        if c.is_some() {
+            self.pos += 1;
+        }
+        c
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn parse_alternation(&mut self) -> Result<Node, Error> {
+        let mut branches = vec![self.parse_concat()?];
+        while self.eat('|') {
+            branches.push(self.parse_concat()?);
+        }
+
+        if branches.len() == 1 {
+            Ok(branches.pop().unwrap())
+        } else {
+            Ok(Node::Alternate(branches))
+        }
+    }
+
+    fn parse_concat(&mut self) -> Result<Node, Error> {
+        let mut nodes = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            nodes.push(self.parse_repeat()?);
+        }
+
+        match nodes.len() {
+            0 => Ok(Node::Empty),
+            1 => Ok(nodes.pop().unwrap()),
+            _ => Ok(Node::Concat(nodes)),
+        }
+    }
+
+    fn parse_repeat(&mut self) -> Result<Node, Error> {
+        let mut node = self.parse_atom()?;
+
+        loop {
+            let start = self.pos;
+            let (min, max) = match self.peek() {
+                Some('*') => { self.pos += 1; (0, None) }
+                Some('+') => { self.pos += 1; (1, None) }
+                Some('?') => { self.pos += 1; (0, Some(1)) }
+                Some('{') => match self.parse_counted()? {
+                    Some(bounds) => bounds,
+                    // Not a valid counted repetition, so '{' is taken literally by the next atom
+                    None => break,
+                },
+                _ => break,
+            };
+
+            if let Node::Empty | Node::Assert(_) = node {
+                self.pos = start;
+                return Err(self.error("nothing to repeat"));
+            }
+
+            let greedy = !self.eat('?');
+            node = Node::Repeat { node: Box::new(node), min, max, greedy };
+        }
+
+        Ok(node)
+    }
+
+    // Parses {n}, {n,} or {n,m}. Returns None and leaves the position untouched if the
+    // braces do not form a repetition.
+    fn parse_counted(&mut self) -> Result<Option<(u32, Option<u32>)>, Error> {
+        let start = self.pos;
+        self.pos += 1;
+
+        let min = self.parse_number();
+        let max = if self.eat(',') { self.parse_number() } else { min };
+        let has_comma = self.chars[start..self.pos].contains(&',');
+
+        if min.is_none() || !self.eat('}') {
+            self.pos = start;
+            return Ok(None);
+        }
+
+        let min = min.unwrap();
+        let max = if has_comma { max } else { Some(min) };
+        if min > MAX_REPEAT || max.is_some_and(|max| max > MAX_REPEAT) {
+            self.pos = start;
+            return Err(self.error("repetition count too large"));
+        }
+        if max.is_some_and(|max| max < min) {
+            self.pos = start;
+            return Err(self.error("invalid repetition range"));
+        }
+
+        Ok(Some((min, max)))
+    }
+
+    fn parse_number(&mut self) -> Option<u32> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return None;
+        }
+        self.chars[start..self.pos].iter().collect::<String>().parse().ok()
+    }
+
+    fn parse_atom(&mut self) -> Result<Node, Error> {
+        let c = match self.next() {
+            Some(c) => c,
+            None => return Ok(Node::Empty),
+        };
+
+        match c {
+            '(' => self.parse_group(),
+            '[' => Ok(Node::Class(self.parse_class()?)),
+            '.' => Ok(Node::Any),
+            '^' if self.multi_line => Ok(Node::Assert(Assertion::LineStart)),
+            '$' if self.multi_line => Ok(Node::Assert(Assertion::LineEnd)),
+            '^' => Ok(Node::Assert(Assertion::Start)),
+            '$' => Ok(Node::Assert(Assertion::End)),
+            '\\' => self.parse_escape(),
+            '*' | '+' | '?' => {
+                self.pos -= 1;
+                Err(self.error("nothing to repeat"))
+            }
+            c => Ok(Node::Literal(c)),
+        }
+    }
+
+    fn parse_group(&mut self) -> Result<Node, Error> {
+        let index = if self.eat('?') {
+            if !self.eat(':') {
+                return Err(self.error("unsupported group flag"));
+            }
+            None
+        } else {
+            self.groups += 1;
+            Some(self.groups)
+        };
+
+        let node = self.parse_alternation()?;
+        if !self.eat(')') {
+            return Err(self.error("unclosed group"));
+        }
+
+        Ok(Node::Group(Box::new(node), index))
+    }
+
+    fn parse_escape(&mut self) -> Result<Node, Error> {
+        let c = match self.next() {
+            Some(c) => c,
+            None => return Err(self.error("trailing backslash")),
+        };
+
+        match c {
+            'b' => Ok(Node::Assert(Assertion::WordBoundary)),
+            'B' => Ok(Node::Assert(Assertion::NotWordBoundary)),
+            _ => match self.escape_item(c)? {
+                ClassItem::Range(c, _) => Ok(Node::Literal(c)),
+                item => Ok(Node::Class(Class { items: vec![item], negated: false })),
+            },
+        }
+    }
+
+    // Escapes that are valid both inside and outside of brackets.
+    // Literal characters are returned as a single character range.
+    fn escape_item(&mut self, c: char) -> Result<ClassItem, Error> {
+        let item = match c {
+            'd' => ClassItem::Perl(Perl::Digit, false),
+            'D' => ClassItem::Perl(Perl::Digit, true),
+            'w' => ClassItem::Perl(Perl::Word, false),
+            'W' => ClassItem::Perl(Perl::Word, true),
+            's' => ClassItem::Perl(Perl::Space, false),
+            'S' => ClassItem::Perl(Perl::Space, true),
+            'n' => ClassItem::Range('\n', '\n'),
+            't' => ClassItem::Range('\t', '\t'),
+            'r' => ClassItem::Range('\r', '\r'),
+            c if c.is_alphanumeric() => {
+                self.pos -= 1;
+                return Err(self.error("unknown escape sequence"));
+            }
+            c => ClassItem::Range(c, c),
+        };
+        Ok(item)
+    }
+
+    fn parse_class(&mut self) -> Result<Class, Error> {
+        let start = self.pos - 1;
+        let negated = self.eat('^');
+        let mut items = Vec::new();
+
+        // A ']' right after the opening bracket is a literal
+        if self.eat(']') {
+            items.push(ClassItem::Range(']', ']'));
+        }
+
+        loop {
+            let c = match self.next() {
+                Some(c) => c,
+                None => {
+                    self.pos = start;
+                    return Err(self.error("unclosed character class"));
+                }
+            };
+
+            let low = match c {
+                ']' => break,
+                '[' if self.peek() == Some(':') => {
+                    items.push(self.parse_posix_class()?);
+                    continue;
+                }
+                '\\' => {
+                    let escaped = self.next().ok_or_else(|| self.error("trailing backslash"))?;
+                    match self.escape_item(escaped)? {
+                        ClassItem::Range(c, _) => c,
+                        item => {
+                            items.push(item);
+                            continue;
+                        }
+                    }
+                }
+                c => c,
+            };
+
+            // A '-' that is last in the class is a literal
+            if self.peek() == Some('-') && self.chars.get(self.pos + 1).is_some_and(|c| *c != ']') {
+                self.pos += 1;
+                let high = match self.next() {
+                    Some('\\') => match self.next().map(|c| self.escape_item(c)) {
+                        Some(Ok(ClassItem::Range(c, _))) => c,
+                        _ => return Err(self.error("invalid range end")),
+                    },
+                    Some(c) => c,
+                    None => return Err(self.error("unclosed character class")),
+                };
+                if high < low {
+                    return Err(self.error("invalid character range"));
+                }
+                items.push(ClassItem::Range(low, high));
+            } else {
+                items.push(ClassItem::Range(low, low));
+            }
+        }
+
+        Ok(Class { items, negated })
+    }
+
+    // Parses a class such as [:alpha:], the opening '[' has already been consumed
+    fn parse_posix_class(&mut self) -> Result<ClassItem, Error> {
+        let start = self.pos;
+        let rest: String = self.chars[self.pos..].iter().collect();
+        // The name starts after the ':' that opens the class, so a class can't be closed by it
+        let end = match rest.get(1..).and_then(|name| name.find(":]")) {
+            Some(end) => end + 1,
+            None => return Err(self.error("unclosed POSIX class")),
+        };
+        let name = &rest[1..end];
+
+        let contains: fn(char) -> bool = match name {
+            "alpha" => |c| c.is_alphabetic(),
+            "digit" => |c| c.is_ascii_digit(),
+            "alnum" => |c| c.is_alphanumeric(),
+            "upper" => |c| c.is_uppercase(),
+            "lower" => |c| c.is_lowercase(),
+            "space" => |c| c.is_whitespace(),
+            "punct" => |c| c.is_ascii_punctuation(),
+            "xdigit" => |c| c.is_ascii_hexdigit(),
+            "word" => is_word_char,
+            _ => return Err(self.error("unknown POSIX class")),
+        };
+
+        self.pos = start + rest[..end].chars().count() + 2;
+        Ok(ClassItem::Posix(contains))
+    }
+}
+
+// Fails when the program grows past MAX_PROGRAM_SIZE
+fn compile(node: &Node, program: &mut Vec<Inst>) -> Result<(), ()> {
+    if program.len() > MAX_PROGRAM_SIZE {
+        return Err(());
+    }
+    match node {
+        Node::Empty => {}
+        Node::Literal(c) => program.push(Inst::Char(*c)),
+        Node::Any => program.push(Inst::Any),
+        Node::Class(class) => program.push(Inst::Class(class.clone())),
+        Node::Assert(assertion) => program.push(Inst::Assert(*assertion)),
+        Node::Group(node, index) => match index {
+            Some(index) => {
+                program.push(Inst::Save(index * 2));
+                compile(node, program)?;
+                program.push(Inst::Save(index * 2 + 1));
+            }
+            None => compile(node, program)?,
+        },
+        Node::Concat(nodes) => nodes.iter().try_for_each(|node| compile(node, program))?,
+        Node::Alternate(branches) => {
+            let mut jumps = Vec::new();
+            for (i, branch) in branches.iter().enumerate() {
+                if i == branches.len() - 1 {
+                    compile(branch, program)?;
+                    break;
+                }
+                let split = program.len();
+                program.push(Inst::Split(split + 1, 0));
+                compile(branch, program)?;
+                jumps.push(program.len());
+                program.push(Inst::Jump(0));
+                program[split] = Inst::Split(split + 1, program.len());
+            }
+
+            let end = program.len();
+            for jump in jumps {
+                program[jump] = Inst::Jump(end);
+            }
+        }
+        Node::Repeat { node, min, max, greedy } => {
+            for _ in 0..*min {
+                compile(node, program)?;
+            }
+
+            match max {
+                // x* : L1: split L2, L3; L2: x; jump L1; L3:
+                None => {
+                    let split = program.len();
+                    program.push(Inst::Split(0, 0));
+                    compile(node, program)?;
+                    program.push(Inst::Jump(split));
+                    program[split] = split_inst(split + 1, program.len(), *greedy);
+                }
+                // x{0,n} : each optional copy can skip straight to the end
+                Some(max) => {
+                    let mut splits = Vec::new();
+                    for _ in *min..*max {
+                        splits.push(program.len());
+                        program.push(Inst::Split(0, 0));
+                        compile(node, program)?;
+                    }
+
+                    let end = program.len();
+                    for split in splits {
+                        program[split] = split_inst(split + 1, end, *greedy);
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn split_inst(body: usize, skip: usize, greedy: bool) -> Inst {
+    if greedy {
+        Inst::Split(body, skip)
+    } else {
+        Inst::Split(skip, body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(pattern: &str, text: &str) -> Option<(usize, usize)> {
+        Regex::new(pattern).unwrap().find(text)
+    }
+
+    #[test]
+    fn literal_and_any() {
+        assert_eq!(Some((1, 4)), find("ust", "Rust:"));
+        assert_eq!(Some((0, 3)), find("R.s", "Rust:"));
+        assert_eq!(None, find("a.b", "a\nb"));
+    }
+
+    #[test]
+    fn character_classes() {
+        assert_eq!(Some((5, 8)), find("[0-9]+", "line 123"));
+        assert_eq!(Some((0, 4)), find("[^ ]+", "line 123"));
+        assert_eq!(Some((5, 7)), find(r"\d\d", "line 123"));
+        assert_eq!(Some((0, 1)), find("[]a]", "]"));
+        assert_eq!(Some((0, 2)), find("[a-]+", "-a"));
+        assert_eq!(Some((0, 4)), find("[[:alpha:]]+", "line 123"));
+        assert_eq!(Some((4, 5)), find(r"[\s]", "line 123"));
+    }
+
+    #[test]
+    fn anchors_and_word_boundaries() {
+        assert_eq!(Some((0, 4)), find("^Rust", "Rust Trust"));
+        assert_eq!(None, find("^Trust", "Rust Trust"));
+        assert_eq!(Some((5, 10)), find("Trust$", "Rust Trust"));
+        assert_eq!(Some((0, 4)), find(r"\bRust\b", "Rust Trust"));
+        assert_eq!(Some((1, 4)), find(r"\Bust", "Rust Trust"));
+        assert_eq!(None, find(r"\brust", "Trust"));
+    }
+
+    #[test]
+    fn multi_line_anchors() {
+        let regex = RegexBuilder::new("^Trust$").multi_line(true).build().unwrap();
+        assert_eq!(Some((5, 10)), regex.find("Rust\nTrust\r\nme"));
+        assert_eq!(None, Regex::new("^Trust$").unwrap().find("Rust\nTrust\r\nme"));
+        let regex = RegexBuilder::new(r"fn \w+\(\)\s*\{\n\s+let").multi_line(true).build().unwrap();
+        assert_eq!(Some((0, 19)), regex.find("fn main()\n{\n    let x"));
+    }
+
+    #[test]
+    fn alternation_and_groups() {
+        assert_eq!(Some((0, 3)), find("cat|dog", "cat"));
+        assert_eq!(Some((4, 7)), find("cat|dog", "the dog"));
+        assert_eq!(Some((0, 4)), find("(?:ab)+", "ababa"));
+        assert_eq!(Some((0, 5)), find("gr(a|e)y!", "grey!"));
+    }
+
+    #[test]
+    fn captures() {
+        let regex = Regex::new(r"(\w+)@(\w+)(\.com)?").unwrap();
+        assert_eq!(Some(vec![Some((3, 10)), Some((3, 6)), Some((7, 10)), None]), regex.captures_at("to ann@foo.org", 0));
+        assert_eq!(None, regex.captures_at("to ann@foo.org", 8));
+    }
+
+    #[test]
+    fn repetition() {
+        assert_eq!(Some((0, 4)), find("a{2,4}", "aaaaa"));
+        assert_eq!(Some((0, 2)), find("a{2,4}?", "aaaaa"));
+        assert_eq!(Some((0, 3)), find("a{3}", "aaaaa"));
+        assert_eq!(Some((0, 5)), find("a{2,}", "aaaaa"));
+        assert_eq!(Some((0, 1)), find("a+?", "aaaaa"));
+        assert_eq!(Some((0, 0)), find("x*", "aaaaa"));
+        assert_eq!(Some((0, 4)), find("a{x}", "a{x}"));
+    }
+
+    #[test]
+    fn pathological_pattern_does_not_blow_up() {
+        let text = "a".repeat(30);
+        assert_eq!(None, find("(a*)*b", &text));
+        assert_eq!(None, find("(a|aa)+$b", &text));
+    }
+
+    #[test]
+    fn case_insensitive_matching() {
+        let regex = RegexBuilder::new("rust|[A-C]x").case_insensitive(true).build().unwrap();
+        assert_eq!(Some((1, 5)), regex.find("TRUST"));
+        assert_eq!(Some((0, 2)), regex.find("bX"));
+
+        let regex = RegexBuilder::new("stra[ß]e").case_insensitive(true).build().unwrap();
+        assert_eq!(Some((0, 8)), regex.find("STRAẞE"));
+    }
+
+    #[test]
+    fn word_matching() {
+        let regex = RegexBuilder::new("foo|foobar|-x").word(true).build().unwrap();
+        assert_eq!(Some((0, 6)), regex.find("foobar baz"));
+        assert_eq!(None, regex.find("foobarbaz"));
+        assert_eq!(Some((2, 4)), regex.find("a -x"));
+        assert_eq!(None, regex.find("a -xy"));
+    }
+
+    #[test]
+    fn invalid_patterns() {
+        assert_eq!("nothing to repeat", Regex::new("*a").unwrap_err().message);
+        assert_eq!("unclosed group", Regex::new("(ab").unwrap_err().message);
+        assert_eq!("unmatched ')'", Regex::new("ab)").unwrap_err().message);
+        assert_eq!("unclosed character class", Regex::new("[ab").unwrap_err().message);
+        assert_eq!("invalid repetition range", Regex::new("a{3,1}").unwrap_err().message);
+        assert_eq!("unclosed POSIX class", Regex::new("[[:]").unwrap_err().message);
+        assert_eq!("unknown POSIX class", Regex::new("[[:]:]").unwrap_err().message);
+    }
+
+    #[test]
+    fn long_chains_of_optional_parts_match() {
+        let regex = Regex::new("(?:(?:a?){1000}){49}x").unwrap();
+        assert_eq!(Some((0, 4)), regex.find("aaax"));
+        assert_eq!(None, regex.find("aaa"));
+    }
+
+    #[test]
+    fn nested_repetitions_are_bounded() {
+        assert!(Regex::new("(a{1000}){50}").is_ok());
+        let error = Regex::new("((a{1000}){1000}){1000}").unwrap_err();
+        assert_eq!("regex too large once its repetitions are expanded", error.message);
+        assert!(Regex::new("(?:a{0,1000}|b){1000}").is_err());
+    }
+}
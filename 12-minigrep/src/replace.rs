@@ -0,0 +1,189 @@
+// Search and replace for --replace and --in-place.
+//
+// In regex mode the replacement text can refer to capturing groups: `$1` or `${1}` insert what the
+// group matched, `$0` the whole match and `$$` a literal '$'. A group that doesn't exist or didn't
+// take part in the match inserts nothing. Without --regex the replacement text is used as is.
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+use std::process;
+
+use crate::matcher::Matcher;
+use crate::regex::Captures;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Literal(String),
+    Group(usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replacement {
+    parts: Vec<Part>,
+}
+
+impl Replacement {
+    pub fn new(text: &str, captures: bool) -> Replacement {
+        if !captures {
+            return Replacement { parts: vec![Part::Literal(String::from(text))] };
+        }
+
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = text.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            if c != '$' {
+                literal.push(c);
+                continue;
+            }
+
+            let braced = chars.next_if_eq(&'{').is_some();
+            let mut digits = String::new();
+            while let Some(digit) = chars.next_if(|c| c.is_ascii_digit()) {
+                digits.push(digit);
+            }
+            let closed = !braced || chars.next_if_eq(&'}').is_some();
+
+            match digits.parse() {
+                Ok(group) if closed => {
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Group(group));
+                }
+                _ => {
+                    // Not a group reference, keep the text as it was. `$$` is an escaped '$'.
+                    if !braced && digits.is_empty() && chars.next_if_eq(&'$').is_some() {
+                        literal.push('$');
+                        continue;
+                    }
+                    literal.push('$');
+                    if braced {
+                        literal.push('{');
+                    }
+                    literal.push_str(&digits);
+                    if braced && closed {
+                        literal.push('}');
+                    }
+                }
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+
+        Replacement { parts }
+    }
+
+    // Appends the replacement for one match of `text` to `out`
+    pub fn expand(&self, text: &str, captures: &Captures, out: &mut String) {
+        for part in &self.parts {
+            match part {
+                Part::Literal(literal) => out.push_str(literal),
+                Part::Group(group) => {
+                    if let Some(Some((start, end))) = captures.get(*group) {
+                        out.push_str(&text[*start..*end]);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Replaces every match in every line of `contents`, keeping the line endings as they are
+pub fn replace_lines(matcher: &Matcher, replacement: &Replacement, contents: &str) -> String {
+    let mut replaced = String::with_capacity(contents.len());
+
+    for line in contents.split_inclusive('\n') {
+        let text = line.strip_suffix('\n').unwrap_or(line);
+        let text = text.strip_suffix('\r').unwrap_or(text);
+        replaced.push_str(&matcher.replace(text, replacement));
+        replaced.push_str(&line[text.len()..]);
+    }
+
+    replaced
+}
+
+// Replaces the file's contents. They are written to a temporary file in the same directory first,
+// which is then renamed over the original, so the file is never seen half written.
+// With a backup suffix, a copy of the original is kept next to it under the name plus the suffix.
+pub fn write_atomically(path: &Path, contents: &str, backup_suffix: Option<&str>) -> io::Result<()> {
+    let name = path.file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file name"))?;
+    let with_suffix = |suffix: &str| {
+        let mut name = OsString::from(name);
+        name.push(suffix);
+        path.with_file_name(name)
+    };
+    let temp = with_suffix(&format!(".minigrep-{}.tmp", process::id()));
+
+    let result = File::create(&temp)
+        .and_then(|mut file| {
+            file.write_all(contents.as_bytes())?;
+            file.set_permissions(fs::metadata(path)?.permissions())?;
+            file.sync_all()
+        })
+        .and_then(|_| match backup_suffix {
+            Some(suffix) => fs::copy(path, with_suffix(suffix)).map(|_| ()),
+            None => Ok(()),
+        })
+        .and_then(|_| fs::rename(&temp, path));
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+    use std::env;
+
+    fn expand(replacement: &str, captures: bool) -> String {
+        let text = "ann@foo";
+        let groups = vec![Some((0, 7)), Some((0, 3)), None];
+        let mut out = String::new();
+        Replacement::new(replacement, captures).expand(text, &groups, &mut out);
+        out
+    }
+
+    #[test]
+    fn group_references() {
+        assert_eq!("<ann@foo>", expand("<$0>", true));
+        assert_eq!("ann at ", expand("$1 at $2", true));
+        assert_eq!("ann0", expand("${1}0", true));
+        assert_eq!("", expand("$10", true));
+    }
+
+    #[test]
+    fn text_that_is_not_a_reference() {
+        assert_eq!("$ $x ${1 ${x} $", expand("$$ $x ${1 ${x} $", true));
+        assert_eq!("$1", expand("$1", false));
+    }
+
+    #[test]
+    fn replace_lines_keeps_line_endings() {
+        let config = Config { patterns: vec![String::from("o")], case_sensitive: true, ..Config::default() };
+        let matcher = Matcher::new(&config).unwrap();
+        let replacement = Replacement::new("0", false);
+        assert_eq!("f00\r\nbar\nb0", replace_lines(&matcher, &replacement, "foo\r\nbar\nbo"));
+    }
+
+    #[test]
+    fn write_atomically_keeps_a_backup() {
+        let dir = env::temp_dir().join(format!("minigrep-replace-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("poem.txt");
+        fs::write(&path, "old").unwrap();
+
+        write_atomically(&path, "new", Some(".bak")).unwrap();
+        assert_eq!("new", fs::read_to_string(&path).unwrap());
+        assert_eq!("old", fs::read_to_string(dir.join("poem.txt.bak")).unwrap());
+        assert_eq!(2, fs::read_dir(&dir).unwrap().count());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,349 @@
+// The library interface: a Searcher runs searches and hands the results to a Sink,
+// so other programs can use minigrep without going through stdout.
+//
+//     let searcher = SearcherBuilder::new().pattern("nobody").case_insensitive(true).context(1).build()?;
+//     let stats = searcher.search_path(Path::new("poem.txt"), &mut my_sink)?;
+//
+// The sink is called with every matching line and context line, and with the stats of each input.
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+
+use crate::binary::{self, BinaryFiles, BinarySink};
+use crate::buffer::search_buffer;
+use crate::decompress;
+use crate::encoding::{self, Encoding};
+use crate::matcher::Matcher;
+use crate::mmap::Mmap;
+use crate::multiline::search_multiline;
+use crate::regex;
+use crate::sink::{Sink, Stats};
+use crate::stream::search_reader;
+use crate::Config;
+
+pub struct SearcherBuilder {
+    // The search options are kept in the same form as the command line ones
+    config: Config,
+}
+
+impl SearcherBuilder {
+    pub fn new() -> SearcherBuilder {
+        SearcherBuilder { config: Config { case_sensitive: true, ..Config::default() } }
+    }
+
+    // Adds a pattern, lines matching any of the patterns are selected
+    pub fn pattern(mut self, pattern: &str) -> SearcherBuilder {
+        self.config.patterns.push(String::from(pattern));
+        self
+    }
+
+    pub fn case_insensitive(mut self, yes: bool) -> SearcherBuilder {
+        self.config.case_sensitive = !yes;
+        self
+    }
+
+    // Treat the patterns as regular expressions
+    pub fn regex(mut self, yes: bool) -> SearcherBuilder {
+        self.config.regex = yes;
+        self
+    }
+
+    pub fn word(mut self, yes: bool) -> SearcherBuilder {
+        self.config.word_regexp = yes;
+        self
+    }
+
+    // Match against the whole input, so that matches can span lines
+    pub fn multiline(mut self, yes: bool) -> SearcherBuilder {
+        self.config.multiline = yes;
+        self
+    }
+
+    pub fn invert_match(mut self, yes: bool) -> SearcherBuilder {
+        self.config.invert_match = yes;
+        self
+    }
+
+    // Match approximately, within `max_distance` edits of a pattern
+    pub fn fuzzy(mut self, max_distance: Option<usize>) -> SearcherBuilder {
+        self.config.fuzzy = max_distance;
+        self
+    }
+
+    // The encoding of the input, None detects UTF-16 by its byte order mark and reads anything else as UTF-8
+    pub fn encoding(mut self, encoding: Option<Encoding>) -> SearcherBuilder {
+        self.config.encoding = encoding;
+        self
+    }
+
+    // What to do with inputs that turn out to be binary, see binary
+    pub fn binary_files(mut self, binary_files: BinaryFiles) -> SearcherBuilder {
+        self.config.binary_files = binary_files;
+        self
+    }
+
+    // Map files into memory instead of reading them, on by default
+    pub fn mmap(mut self, yes: bool) -> SearcherBuilder {
+        self.config.no_mmap = !yes;
+        self
+    }
+
+    // Stop searching an input after this many matching lines
+    pub fn max_count(mut self, max_count: Option<usize>) -> SearcherBuilder {
+        self.config.max_count = max_count;
+        self
+    }
+
+    pub fn before_context(mut self, lines: usize) -> SearcherBuilder {
+        self.config.before_context = lines;
+        self
+    }
+
+    pub fn after_context(mut self, lines: usize) -> SearcherBuilder {
+        self.config.after_context = lines;
+        self
+    }
+
+    pub fn context(self, lines: usize) -> SearcherBuilder {
+        self.before_context(lines).after_context(lines)
+    }
+
+    pub fn build(self) -> Result<Searcher, regex::Error> {
+        Searcher::from_config(&self.config)
+    }
+}
+
+impl Default for SearcherBuilder {
+    fn default() -> SearcherBuilder {
+        SearcherBuilder::new()
+    }
+}
+
+pub struct Searcher {
+    matcher: Matcher,
+    before_context: usize,
+    after_context: usize,
+    max_count: Option<usize>,
+    // For binary inputs, which only print whether they match, unless their lines are counted
+    binary_max_count: Option<usize>,
+    multiline: bool,
+    mmap: bool,
+    encoding: Option<Encoding>,
+    binary_files: BinaryFiles,
+}
+
+impl Searcher {
+    pub fn from_config(config: &Config) -> Result<Searcher, regex::Error> {
+        Ok(Searcher {
+            matcher: Matcher::new(config)?,
+            before_context: config.before_context,
+            after_context: config.after_context,
+            max_count: config.max_count,
+            binary_max_count: if config.count { config.max_count } else { Some(config.max_count.unwrap_or(1).min(1)) },
+            multiline: config.multiline,
+            mmap: !config.no_mmap,
+            encoding: config.encoding,
+            binary_files: config.binary_files,
+        })
+    }
+
+    // The matcher can find the matches within a line, e.g. to highlight them
+    pub fn matcher(&self) -> &Matcher {
+        &self.matcher
+    }
+
+    pub fn encoding(&self) -> Option<Encoding> {
+        self.encoding
+    }
+
+    // Searches a file, which is decompressed first if it is compressed.
+    // Other UTF-8 files are mapped into memory and searched as a whole, unless mmap is off.
+    pub fn search_path(&self, path: &Path, sink: &mut dyn Sink) -> io::Result<Stats> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let start = reader.fill_buf()?;
+        if self.mmap && decompress::detect(start).is_none() && encoding::detect(start, self.encoding).0 == Encoding::Utf8 {
+            if let Some(map) = Mmap::map(reader.get_ref())? {
+                return self.search_bytes(path, &map, sink);
+            }
+        }
+        self.search_reader(path, decompress::decoder(reader)?, sink)
+    }
+
+    // Searches text that is already in memory, `path` is only passed on to the sink
+    pub fn search_str(&self, path: &Path, text: &str, sink: &mut dyn Sink) -> io::Result<Stats> {
+        self.search_bytes(path, text.as_bytes(), sink)
+    }
+
+    // Like search_str, for bytes in the searcher's encoding
+    pub fn search_bytes(&self, path: &Path, bytes: &[u8], sink: &mut dyn Sink) -> io::Result<Stats> {
+        let (encoding, bom) = encoding::detect(bytes, self.encoding);
+        if encoding != Encoding::Utf8 {
+            return self.search_reader(path, bytes, sink);
+        }
+        let bytes = &bytes[bom..];
+        self.search_text(path, binary::is_binary(bytes), sink, |sink, max_count| {
+            if self.multiline {
+                return self.search_whole(path, &String::from_utf8_lossy(bytes), sink, max_count);
+            }
+            sink.begin(path)?;
+            let stats = search_buffer(&self.matcher, bytes, self.before_context, self.after_context, max_count, |line| sink.line(path, line))?;
+            sink.end(path, &stats)?;
+            Ok(stats)
+        })
+    }
+
+    // Searches any reader, such as stdin. `path` is only passed on to the sink.
+    pub fn search_reader<R: BufRead>(&self, path: &Path, reader: R, sink: &mut dyn Sink) -> io::Result<Stats> {
+        let mut reader = encoding::decoder(reader, self.encoding)?;
+        let is_binary = binary::is_binary(reader.fill_buf()?);
+        self.search_text(path, is_binary, sink, |sink, max_count| {
+            if self.multiline {
+                let mut bytes = Vec::new();
+                reader.read_to_end(&mut bytes)?;
+                return self.search_whole(path, &String::from_utf8_lossy(&bytes), sink, max_count);
+            }
+            sink.begin(path)?;
+            let stats = search_reader(&self.matcher, reader, self.before_context, self.after_context, max_count, |line| sink.line(path, line))?;
+            sink.end(path, &stats)?;
+            Ok(stats)
+        })
+    }
+
+    // Runs `search` with the max count to use, unless the input is binary and binary inputs are not
+    // searched. Binary inputs that are searched only tell the sink whether they match, so unless
+    // their lines are counted, the search stops at the first match.
+    fn search_text<F>(&self, path: &Path, is_binary: bool, sink: &mut dyn Sink, search: F) -> io::Result<Stats>
+    where
+        F: FnOnce(&mut dyn Sink, Option<usize>) -> io::Result<Stats>,
+    {
+        if !is_binary || self.binary_files == BinaryFiles::Text {
+            return search(sink, self.max_count);
+        }
+        match self.binary_files {
+            // Like an input without a match
+            BinaryFiles::WithoutMatch => {
+                let stats = Stats { searches: 1, ..Stats::default() };
+                sink.begin(path)?;
+                sink.end(path, &stats)?;
+                Ok(stats)
+            }
+            _ => search(&mut BinarySink::new(sink), self.binary_max_count),
+        }
+    }
+
+    // Searches the whole input at once, so that matches can span lines
+    fn search_whole(&self, path: &Path, text: &str, sink: &mut dyn Sink, max_count: Option<usize>) -> io::Result<Stats> {
+        sink.begin(path)?;
+        let stats = search_multiline(&self.matcher, text, self.before_context, self.after_context, max_count, |line| sink.line(path, line))?;
+        sink.end(path, &stats)?;
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::ContextLine;
+
+    // Collects the results the way another program would
+    #[derive(Default)]
+    struct Lines {
+        lines: Vec<(usize, String, bool)>,
+        stats: Stats,
+    }
+
+    impl Sink for Lines {
+        fn line(&mut self, _path: &Path, line: &ContextLine) -> io::Result<()> {
+            self.lines.push((line.number, String::from(line.text), line.is_match));
+            Ok(())
+        }
+
+        fn end(&mut self, _path: &Path, stats: &Stats) -> io::Result<()> {
+            self.stats = *stats;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn searches_with_context() {
+        let searcher = SearcherBuilder::new().pattern("FROG").case_insensitive(true).before_context(1).build().unwrap();
+        let mut sink = Lines::default();
+        let stats = searcher.search_path(Path::new("poem.txt"), &mut sink).unwrap();
+
+        let expected = vec![(6, String::from("How dreary to be somebody!"), false), (7, String::from("How public, like a frog"), true)];
+        assert_eq!(expected, sink.lines);
+        assert_eq!((1, 1), (stats.matched_lines, sink.stats.matched_lines));
+    }
+
+    #[test]
+    fn searches_text_in_memory() {
+        let searcher = SearcherBuilder::new().pattern("us").pattern("pick").regex(true).word(true).build().unwrap();
+        let mut sink = Lines::default();
+        searcher.search_str(Path::new("text"), "Rust:\nPick three.\nTrust us.", &mut sink).unwrap();
+        assert_eq!(vec![(3, String::from("Trust us."), true)], sink.lines);
+    }
+
+    #[test]
+    fn mapped_and_read_files_give_the_same_results() {
+        let mut results = Vec::new();
+        for mmap in [true, false] {
+            let searcher = SearcherBuilder::new().pattern("body").mmap(mmap).build().unwrap();
+            let mut sink = Lines::default();
+            searcher.search_path(Path::new("poem.txt"), &mut sink).unwrap();
+            results.push((sink.lines, sink.stats));
+        }
+        assert_eq!(results[0], results[1]);
+        assert_eq!(3, results[0].1.matched_lines);
+    }
+
+    #[test]
+    fn searches_utf16_text() {
+        let text = "I'm nobody! Who are you?\r\nAre you nobody, too?\r\n";
+        let bytes: Vec<u8> = "\u{feff}".encode_utf16().chain(text.encode_utf16()).flat_map(|unit| unit.to_le_bytes()).collect();
+        let searcher = SearcherBuilder::new().pattern("too").build().unwrap();
+        let mut sink = Lines::default();
+        searcher.search_bytes(Path::new("text"), &bytes, &mut sink).unwrap();
+        assert_eq!(vec![(2, String::from("Are you nobody, too?"), true)], sink.lines);
+    }
+
+    #[test]
+    fn multiline_matches_span_lines() {
+        for mmap in [true, false] {
+            let searcher = SearcherBuilder::new().pattern(r"somebody!\nHow").regex(true).multiline(true).mmap(mmap).build().unwrap();
+            let mut sink = Lines::default();
+            searcher.search_path(Path::new("poem.txt"), &mut sink).unwrap();
+            let expected = vec![(6, String::from("How dreary to be somebody!"), true), (7, String::from("How public, like a frog"), true)];
+            assert_eq!(expected, sink.lines);
+        }
+    }
+
+    #[test]
+    fn binary_inputs_follow_binary_files() {
+        let bytes = b"ELF\0\x01\nnobody\n";
+        let expected = [(vec![], 1), (vec![], 0), (vec![(2, String::from("nobody"), true)], 1)];
+        for (binary_files, (lines, matched_lines)) in [BinaryFiles::Binary, BinaryFiles::WithoutMatch, BinaryFiles::Text].into_iter().zip(expected) {
+            for multiline in [false, true] {
+                let searcher = SearcherBuilder::new().pattern("nobody").binary_files(binary_files).multiline(multiline).build().unwrap();
+                let mut sink = Lines::default();
+                searcher.search_bytes(Path::new("a.out"), bytes, &mut sink).unwrap();
+                assert_eq!((&lines, matched_lines), (&sink.lines, sink.stats.matched_lines));
+            }
+        }
+    }
+
+    #[test]
+    fn binary_inputs_stop_at_the_first_match_unless_counted() {
+        let bytes = b"ELF\0\x01\nnobody\nnobody\nnobody\n";
+        for (count, matched_lines) in [(false, 1), (true, 3)] {
+            let config = Config { patterns: vec![String::from("nobody")], count, ..Config::default() };
+            let mut sink = Lines::default();
+            Searcher::from_config(&config).unwrap().search_bytes(Path::new("a.out"), bytes, &mut sink).unwrap();
+            assert_eq!(matched_lines, sink.stats.matched_lines);
+        }
+    }
+
+    #[test]
+    fn invalid_regex_fails_to_build() {
+        assert!(SearcherBuilder::new().pattern("(nobody").regex(true).build().is_err());
+    }
+}
@@ -0,0 +1,45 @@
+// Where search results go. The text printer and the JSON printer are both sinks,
+// and programs using minigrep as a library can implement their own (see searcher).
+use std::io;
+use std::path::Path;
+
+use crate::context::ContextLine;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Stats {
+    // Number of inputs searched, and how many of them contained a match
+    pub searches: usize,
+    pub searches_with_match: usize,
+    pub matched_lines: usize,
+    pub bytes_searched: usize,
+}
+
+impl Stats {
+    pub fn add(&mut self, other: &Stats) {
+        self.searches += other.searches;
+        self.searches_with_match += other.searches_with_match;
+        self.matched_lines += other.matched_lines;
+        self.bytes_searched += other.bytes_searched;
+    }
+}
+
+// An error returned by a sink stops the search and is returned by it
+pub trait Sink {
+    // Called before the lines of each input
+    fn begin(&mut self, _path: &Path) -> io::Result<()> {
+        Ok(())
+    }
+
+    // Called for every matching line and every context line, in order
+    fn line(&mut self, path: &Path, line: &ContextLine) -> io::Result<()>;
+
+    // Called instead of `line` for a binary input with a match, before `end` (see binary)
+    fn binary_match(&mut self, _path: &Path) -> io::Result<()> {
+        Ok(())
+    }
+
+    // Called after each input with the numbers for that input
+    fn end(&mut self, _path: &Path, _stats: &Stats) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,181 @@
+// Line by line search over any BufRead, such as a file or stdin.
+//
+// Only the current line and the lines kept for before-context are held in memory,
+// so the size of the input doesn't matter.
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::io::{self, BufRead};
+
+use crate::context::ContextLine;
+use crate::matcher::Matcher;
+use crate::sink::Stats;
+
+// Calls `output` for every matching line and its context lines, in order.
+// With `max_count`, reading stops after that many matching lines and their after-context.
+pub fn search_reader<R, F>(
+    matcher: &Matcher,
+    mut reader: R,
+    before: usize,
+    after: usize,
+    max_count: Option<usize>,
+    mut output: F,
+) -> io::Result<Stats>
+where
+    R: BufRead,
+    F: FnMut(&ContextLine) -> io::Result<()>,
+{
+    // The buffers are reused for every line to avoid an allocation per line
+    let mut buffer = Vec::new();
+    let mut previous: VecDeque<(usize, usize, String)> = VecDeque::with_capacity(before);
+    let mut after_left = 0;
+    let mut stats = Stats { searches: 1, ..Stats::default() };
+    let mut number = 0;
+
+    loop {
+        let done = max_count.is_some_and(|max| stats.matched_lines >= max);
+        if done && after_left == 0 {
+            break;
+        }
+        buffer.clear();
+        let offset = stats.bytes_searched;
+        let length = reader.read_until(b'\n', &mut buffer)?;
+        if length == 0 {
+            break;
+        }
+        stats.bytes_searched += length;
+        number += 1;
+        let line = to_line(&buffer);
+        let line = line.as_ref();
+
+        if !done && matcher.is_match(line) {
+            stats.matched_lines += 1;
+            for (number, offset, text) in previous.drain(..) {
+                output(&ContextLine { number, offset, text: &text, is_match: false })?;
+            }
+            output(&ContextLine { number, offset, text: line, is_match: true })?;
+            after_left = after;
+        } else if after_left > 0 {
+            after_left -= 1;
+            output(&ContextLine { number, offset, text: line, is_match: false })?;
+        } else if before > 0 {
+            let mut text = if previous.len() == before {
+                previous.pop_front().unwrap().2
+            } else {
+                String::new()
+            };
+            text.clear();
+            text.push_str(line);
+            previous.push_back((number, offset, text));
+        }
+    }
+
+    stats.searches_with_match = (stats.matched_lines > 0) as usize;
+    Ok(stats)
+}
+
+// Strips the line ending, like str::lines does. Bytes that are not valid UTF-8 become U+FFFD.
+fn to_line(buffer: &[u8]) -> Cow<'_, str> {
+    let buffer = buffer.strip_suffix(b"\n").unwrap_or(buffer);
+    let buffer = buffer.strip_suffix(b"\r").unwrap_or(buffer);
+    String::from_utf8_lossy(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::search_context;
+    use crate::Config;
+
+    const CONTENTS: &str = "\
+one
+two match
+three\r
+four
+five
+six match
+seven match
+eight
+nine
+ten match
+";
+
+    fn matcher() -> Matcher {
+        let config = Config { patterns: vec![String::from("match")], case_sensitive: true, ..Config::default() };
+        Matcher::new(&config).unwrap()
+    }
+
+    fn collect(before: usize, after: usize) -> (Stats, Vec<(usize, String, bool)>) {
+        let mut lines = Vec::new();
+        let stats = search_reader(&matcher(), CONTENTS.as_bytes(), before, after, None, |line| {
+            lines.push((line.number, String::from(line.text), line.is_match));
+            Ok(())
+        }).unwrap();
+        (stats, lines)
+    }
+
+    #[test]
+    fn search_reader_returns_matching_lines() {
+        let (stats, lines) = collect(0, 0);
+        let expected_stats = Stats { searches: 1, searches_with_match: 1, matched_lines: 4, bytes_searched: CONTENTS.len() };
+        assert_eq!(expected_stats, stats);
+        let expected: Vec<_> = [(2, "two match"), (6, "six match"), (7, "seven match"), (10, "ten match")]
+            .iter()
+            .map(|(number, text)| (*number, String::from(*text), true))
+            .collect();
+        assert_eq!(expected, lines);
+    }
+
+    #[test]
+    fn search_reader_agrees_with_search_context() {
+        for (before, after) in [(1, 0), (0, 2), (2, 1), (3, 3)] {
+            let expected: Vec<_> = search_context(&matcher(), CONTENTS, before, after)
+                .into_iter()
+                .flatten()
+                .map(|line| (line.number, String::from(line.text), line.is_match))
+                .collect();
+            assert_eq!(expected, collect(before, after).1);
+        }
+    }
+
+    #[test]
+    fn search_reader_reports_line_offsets() {
+        let mut offsets = Vec::new();
+        search_reader(&matcher(), CONTENTS.as_bytes(), 0, 0, None, |line| {
+            offsets.push(line.offset);
+            Ok(())
+        }).unwrap();
+        let expected: Vec<_> = CONTENTS.match_indices("match").map(|(index, _)| CONTENTS[..index].rfind('\n').unwrap() + 1).collect();
+        assert_eq!(expected, offsets);
+    }
+
+    #[test]
+    fn search_reader_stops_after_max_count() {
+        let mut lines = Vec::new();
+        let stats = search_reader(&matcher(), CONTENTS.as_bytes(), 0, 1, Some(2), |line| {
+            lines.push((line.number, line.is_match));
+            Ok(())
+        }).unwrap();
+        // The after-context of the last match is still printed, even when it matches
+        assert_eq!(vec![(2, true), (3, false), (6, true), (7, false)], lines);
+        assert_eq!(2, stats.matched_lines);
+
+        let stats = search_reader(&matcher(), CONTENTS.as_bytes(), 0, 0, Some(0), |_| panic!("no lines expected")).unwrap();
+        assert_eq!(0, stats.bytes_searched);
+    }
+
+    #[test]
+    fn search_reader_strips_carriage_returns() {
+        let (_, lines) = collect(0, 1);
+        assert_eq!((3, String::from("three"), false), lines[1]);
+    }
+
+    #[test]
+    fn search_reader_replaces_invalid_utf8() {
+        let mut lines = Vec::new();
+        search_reader(&matcher(), &b"match\n\xff\xfematch\n"[..], 0, 0, None, |line| {
+            lines.push(String::from(line.text));
+            Ok(())
+        }).unwrap();
+        assert_eq!(vec!["match", "\u{fffd}\u{fffd}match"], lines);
+    }
+}
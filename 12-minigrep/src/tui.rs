@@ -0,0 +1,464 @@
+// Interactive mode (--interactive): browse the matches in the terminal and refine the query live.
+//
+// The top of the screen is the query, below it the list of matching lines, and the bottom half
+// previews the selected match with the lines around it. Typing edits the query and searches again.
+// Up and Down (or Ctrl-P and Ctrl-N), Page Up and Page Down move through the matches. Enter quits
+// and prints the selected match like `path:line:text`, Esc or Ctrl-C quit without printing anything.
+//
+// Keys are read from /dev/tty in raw mode, set with stty, so standard output can still be redirected.
+// The screen is drawn with plain ANSI escape sequences on the terminal's alternate screen.
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::color::{self, paint};
+use crate::context::ContextLine;
+use crate::error::MinigrepError;
+use crate::filter::Filter;
+use crate::searcher::Searcher;
+use crate::sink::Sink;
+use crate::{Config, Input};
+
+// Searching stops after this many matching lines, there is no point in listing more
+const MAX_HITS: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Key {
+    Char(char),
+    Backspace,
+    Enter,
+    Escape,
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    // Ctrl and a letter, other than the ones above
+    Ctrl(char),
+}
+
+// Parses the key at the start of `bytes`, and returns how many bytes it took.
+// Returns None when the bytes are only the start of a key and more are needed.
+pub fn parse_key(bytes: &[u8]) -> Option<(Key, usize)> {
+    let key = match bytes {
+        [] => return None,
+        [0x1b, b'[', b'A', ..] => (Key::Up, 3),
+        [0x1b, b'[', b'B', ..] => (Key::Down, 3),
+        [0x1b, b'[', b'5', b'~', ..] => (Key::PageUp, 4),
+        [0x1b, b'[', b'6', b'~', ..] => (Key::PageDown, 4),
+        // Other escape sequences are skipped up to their final byte
+        [0x1b, b'[', rest @ ..] => match rest.iter().position(|b| (0x40..=0x7e).contains(b)) {
+            Some(end) => (Key::Escape, end + 3),
+            None => return None,
+        },
+        [0x1b, ..] => (Key::Escape, 1),
+        [b'\r' | b'\n', ..] => (Key::Enter, 1),
+        [0x7f | 0x08, ..] => (Key::Backspace, 1),
+        [0x10, ..] => (Key::Up, 1),
+        [0x0e, ..] => (Key::Down, 1),
+        [byte @ 0x01..=0x1a, ..] => (Key::Ctrl(char::from(b'a' + byte - 1)), 1),
+        _ => {
+            // A UTF-8 encoded character, which can be split across reads
+            let length = match bytes[0] {
+                0xc0..=0xdf => 2,
+                0xe0..=0xef => 3,
+                0xf0..=0xf7 => 4,
+                _ => 1,
+            };
+            if bytes.len() < length {
+                return None;
+            }
+            let c = std::str::from_utf8(&bytes[..length]).ok().and_then(|text| text.chars().next());
+            (Key::Char(c.unwrap_or(char::REPLACEMENT_CHARACTER)), length)
+        }
+    };
+    Some(key)
+}
+
+// A matching line
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hit {
+    pub path: PathBuf,
+    pub number: usize,
+    pub text: String,
+}
+
+// What the caller should do after a key
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Redraw,
+    // The query changed
+    Search,
+    // Quit, printing the selected match if there is one
+    Accept,
+    Quit,
+}
+
+// The state of the screen, apart from the terminal itself
+#[derive(Debug, Default)]
+pub struct Browser {
+    pub query: String,
+    hits: Vec<Hit>,
+    selected: usize,
+    // Index of the first hit shown in the list
+    top: usize,
+    // Shown instead of the number of matches, e.g. for an invalid regex
+    message: Option<String>,
+    // The lines of the file shown in the preview
+    preview: Option<(PathBuf, Vec<String>)>,
+}
+
+impl Browser {
+    pub fn new(query: &str) -> Browser {
+        Browser { query: String::from(query), ..Browser::default() }
+    }
+
+    pub fn set_hits(&mut self, hits: Vec<Hit>) {
+        self.hits = hits;
+        self.selected = 0;
+        self.top = 0;
+        self.message = None;
+    }
+
+    pub fn set_message(&mut self, message: String) {
+        self.set_hits(Vec::new());
+        self.message = Some(message);
+    }
+
+    pub fn selected(&self) -> Option<&Hit> {
+        self.hits.get(self.selected)
+    }
+
+    // `page` is the number of hits the list shows at once
+    pub fn handle(&mut self, key: Key, page: usize) -> Action {
+        let last = self.hits.len().saturating_sub(1);
+        match key {
+            Key::Char(c) if !c.is_control() => {
+                self.query.push(c);
+                return Action::Search;
+            }
+            Key::Backspace => {
+                return match self.query.pop() {
+                    Some(_) => Action::Search,
+                    None => Action::Redraw,
+                };
+            }
+            Key::Ctrl('u') => {
+                self.query.clear();
+                return Action::Search;
+            }
+            Key::Up => self.selected = self.selected.saturating_sub(1),
+            Key::Down => self.selected = (self.selected + 1).min(last),
+            Key::PageUp => self.selected = self.selected.saturating_sub(page.max(1)),
+            Key::PageDown => self.selected = (self.selected + page.max(1)).min(last),
+            Key::Enter => return Action::Accept,
+            Key::Escape | Key::Ctrl('c') | Key::Ctrl('q') => return Action::Quit,
+            _ => {}
+        }
+        Action::Redraw
+    }
+
+    // Returns the rows of the screen, each at most `width` characters wide not counting colors,
+    // and the column of the cursor in the query
+    pub fn render(&mut self, width: usize, height: usize) -> (Vec<String>, usize) {
+        // One row for the query, one between the list and the preview
+        let list_height = height.saturating_sub(2) / 2;
+        let preview_height = height.saturating_sub(2 + list_height);
+
+        // Scroll the list just enough to show the selected hit
+        if self.selected < self.top {
+            self.top = self.selected;
+        } else if list_height > 0 && self.selected >= self.top + list_height {
+            self.top = self.selected + 1 - list_height;
+        }
+
+        let prompt = format!("> {}", self.query);
+        let status = match &self.message {
+            Some(message) => message.clone(),
+            None if self.hits.len() >= MAX_HITS => format!("{}+ matches", MAX_HITS),
+            None => format!("{} matches", self.hits.len()),
+        };
+        let cursor = prompt.chars().count().min(width);
+        let gap = width.saturating_sub(prompt.chars().count() + status.chars().count()).max(1);
+        let mut rows = vec![truncate(&format!("{}{}{}", prompt, " ".repeat(gap), status), width)];
+
+        for (index, hit) in self.hits.iter().enumerate().skip(self.top).take(list_height) {
+            let row = truncate(&format!("{}:{}:{}", hit.path.display(), hit.number, hit.text), width);
+            rows.push(if index == self.selected { format!("\x1b[7m{}{}", row, color::RESET) } else { row });
+        }
+        rows.resize(1 + list_height, String::new());
+
+        let selected = self.hits.get(self.selected).cloned();
+        let title = match &selected {
+            Some(hit) => format!("── {}:{} ", hit.path.display(), hit.number),
+            None => String::new(),
+        };
+        let fill = "─".repeat(width.saturating_sub(title.chars().count()));
+        rows.push(paint(&truncate(&format!("{}{}", title, fill), width), color::SEPARATOR, true));
+
+        if let Some(hit) = selected {
+            let lines = self.preview_lines(&hit.path);
+            // The selected line is in the middle of the preview when possible
+            let first = hit.number.saturating_sub(preview_height / 2).max(1);
+            for (index, line) in lines.iter().enumerate().skip(first - 1).take(preview_height) {
+                let number = index + 1;
+                let prefix = format!("{:>5} ", number);
+                let row = truncate(&format!("{}{}", prefix, line), width);
+                rows.push(if number == hit.number {
+                    paint(&row, color::MATCH, true)
+                } else {
+                    format!("{}{}", paint(&prefix, color::LINE_NUMBER, true), row.get(prefix.len()..).unwrap_or_default())
+                });
+            }
+        }
+        rows.resize(height, String::new());
+        (rows, cursor)
+    }
+
+    // The lines of the file, read again only when another file is selected
+    fn preview_lines(&mut self, path: &Path) -> &[String] {
+        if self.preview.as_ref().is_none_or(|(previewed, _)| previewed != path) {
+            let lines = match fs::read(path) {
+                Ok(bytes) => String::from_utf8_lossy(&bytes).lines().map(String::from).collect(),
+                Err(e) => vec![format!("couldn't read the file: {}", e)],
+            };
+            self.preview = Some((path.to_path_buf(), lines));
+        }
+        self.preview.as_ref().map(|(_, lines)| lines.as_slice()).unwrap_or_default()
+    }
+}
+
+// Cuts the text to `width` characters, with tabs and other control characters shown as spaces
+fn truncate(text: &str, width: usize) -> String {
+    text.chars().map(|c| if c.is_control() { ' ' } else { c }).take(width).collect()
+}
+
+// Collects the matching lines of every input
+struct Collect {
+    hits: Vec<Hit>,
+}
+
+impl Sink for Collect {
+    fn line(&mut self, path: &Path, line: &ContextLine) -> io::Result<()> {
+        if line.is_match && self.hits.len() < MAX_HITS {
+            self.hits.push(Hit { path: path.to_path_buf(), number: line.number, text: String::from(line.text) });
+        }
+        Ok(())
+    }
+}
+
+fn search(config: &mut Config, inputs: &[Input], query: &str) -> Result<Vec<Hit>, MinigrepError> {
+    if query.is_empty() {
+        return Ok(Vec::new());
+    }
+    config.patterns = vec![String::from(query)];
+    let searcher = Searcher::from_config(config)?;
+
+    let mut sink = Collect { hits: Vec::new() };
+    for input in inputs {
+        if sink.hits.len() >= MAX_HITS {
+            break;
+        }
+        // Files that can't be read are left out, like binary files
+        let _ = crate::search_path(&searcher, &mut sink, input);
+    }
+    Ok(sink.hits)
+}
+
+// The terminal in raw mode, switched back when dropped
+struct Terminal {
+    tty: File,
+    // The settings from `stty -g`, to restore them
+    saved: String,
+}
+
+impl Terminal {
+    fn open() -> io::Result<Terminal> {
+        let tty = OpenOptions::new().read(true).write(true).open("/dev/tty")?;
+        let saved = stty(&tty, &["-g"])?;
+        stty(&tty, &["raw", "-echo"])?;
+        let mut terminal = Terminal { tty, saved: String::from(saved.trim()) };
+        // The alternate screen keeps the shell's screen as it was
+        write!(terminal.tty, "\x1b[?1049h")?;
+        Ok(terminal)
+    }
+
+    // Rows and columns
+    fn size(&self) -> io::Result<(usize, usize)> {
+        let size = stty(&self.tty, &["size"])?;
+        let mut numbers = size.split_whitespace().map(|number| number.parse::<usize>());
+        match (numbers.next(), numbers.next()) {
+            (Some(Ok(rows)), Some(Ok(columns))) => Ok((rows, columns)),
+            _ => Err(io::Error::other(format!("unexpected terminal size '{}'", size.trim()))),
+        }
+    }
+
+    fn draw(&mut self, rows: &[String], cursor: usize) -> io::Result<()> {
+        let mut screen = String::from("\x1b[?25l\x1b[H");
+        for (index, row) in rows.iter().enumerate() {
+            screen.push_str(row);
+            screen.push_str("\x1b[K");
+            if index + 1 < rows.len() {
+                screen.push_str("\r\n");
+            }
+        }
+        screen.push_str(&format!("\x1b[1;{}H\x1b[?25h", cursor + 1));
+        self.tty.write_all(screen.as_bytes())?;
+        self.tty.flush()
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = write!(self.tty, "\x1b[?1049l");
+        let _ = stty(&self.tty, &[&self.saved]);
+    }
+}
+
+// Runs stty on the terminal and returns what it printed
+fn stty(tty: &File, args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty").args(args).stdin(Stdio::from(tty.try_clone()?)).stderr(Stdio::inherit()).output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!("stty {} failed", args.join(" "))));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// Fails with NoMatch when no match was picked
+pub fn run(mut config: Config, filter: &Filter) -> Result<(), MinigrepError> {
+    let mut inputs = Vec::new();
+    for filename in &config.filenames {
+        if filename == "-" {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "standard input can't be searched interactively").into());
+        }
+        // Directories that can't be read are left out, like the files
+        inputs.extend(crate::inputs(Path::new(filename), filter, &mut Vec::new()));
+    }
+    // Config makes sure there is at most one pattern, the query being edited
+    let mut browser = Browser::new(config.patterns.first().map_or("", String::as_str));
+    let mut terminal = Terminal::open()?;
+
+    let mut search_again = true;
+    let mut pending = Vec::new();
+    let mut buffer = [0; 64];
+    let accepted = loop {
+        if search_again {
+            match search(&mut config, &inputs, &browser.query) {
+                Ok(hits) => browser.set_hits(hits),
+                Err(e) => browser.set_message(e.to_string()),
+            }
+            search_again = false;
+        }
+        let (height, width) = terminal.size()?;
+        let (rows, cursor) = browser.render(width, height);
+        terminal.draw(&rows, cursor)?;
+
+        let n = terminal.tty.read(&mut buffer)?;
+        if n == 0 {
+            break None;
+        }
+        pending.extend_from_slice(&buffer[..n]);
+
+        let page = height.saturating_sub(2) / 2;
+        let mut action = Action::Redraw;
+        while let Some((key, length)) = parse_key(&pending) {
+            pending.drain(..length);
+            action = browser.handle(key, page);
+            match action {
+                Action::Search => search_again = true,
+                Action::Accept | Action::Quit => break,
+                Action::Redraw => {}
+            }
+        }
+        match action {
+            Action::Accept => break browser.selected().cloned(),
+            Action::Quit => break None,
+            _ => {}
+        }
+    };
+    drop(terminal);
+
+    match accepted {
+        Some(hit) => {
+            writeln!(io::stdout().lock(), "{}:{}:{}", hit.path.display(), hit.number, hit.text)?;
+            Ok(())
+        }
+        None => Err(MinigrepError::NoMatch),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn hits(count: usize) -> Vec<Hit> {
+        (1..=count).map(|number| Hit { path: PathBuf::from("poem.txt"), number, text: format!("line {}", number) }).collect()
+    }
+
+    #[test]
+    fn keys_are_parsed() {
+        assert_eq!(Some((Key::Char('a'), 1)), parse_key(b"ab"));
+        assert_eq!(Some((Key::Char('ß'), 2)), parse_key("ß".as_bytes()));
+        assert_eq!(None, parse_key(&"ß".as_bytes()[..1]));
+        assert_eq!(Some((Key::Up, 3)), parse_key(b"\x1b[Ax"));
+        assert_eq!(Some((Key::PageDown, 4)), parse_key(b"\x1b[6~"));
+        assert_eq!(None, parse_key(b"\x1b[1;5"));
+        assert_eq!(Some((Key::Escape, 6)), parse_key(b"\x1b[1;5C"));
+        assert_eq!(Some((Key::Escape, 1)), parse_key(b"\x1b"));
+        assert_eq!(Some((Key::Enter, 1)), parse_key(b"\r"));
+        assert_eq!(Some((Key::Backspace, 1)), parse_key(b"\x7f"));
+        assert_eq!(Some((Key::Down, 1)), parse_key(b"\x0e"));
+        assert_eq!(Some((Key::Ctrl('c'), 1)), parse_key(b"\x03"));
+    }
+
+    #[test]
+    fn keys_edit_the_query_and_move_the_selection() {
+        let mut browser = Browser::new("no");
+        browser.set_hits(hits(30));
+        assert_eq!(Action::Search, browser.handle(Key::Char('b'), 10));
+        assert_eq!(Action::Search, browser.handle(Key::Backspace, 10));
+        assert_eq!("no", browser.query);
+
+        assert_eq!(Action::Redraw, browser.handle(Key::Up, 10));
+        assert_eq!(Some(1), browser.selected().map(|hit| hit.number));
+        browser.handle(Key::PageDown, 10);
+        browser.handle(Key::Down, 10);
+        assert_eq!(Some(12), browser.selected().map(|hit| hit.number));
+        browser.handle(Key::PageDown, 100);
+        assert_eq!(Some(30), browser.selected().map(|hit| hit.number));
+
+        assert_eq!(Action::Accept, browser.handle(Key::Enter, 10));
+        assert_eq!(Action::Quit, browser.handle(Key::Escape, 10));
+        assert_eq!(Action::Search, browser.handle(Key::Ctrl('u'), 10));
+        assert_eq!("", browser.query);
+    }
+
+    #[test]
+    fn render_scrolls_to_the_selection_and_previews_it() {
+        let path = env::temp_dir().join(format!("minigrep-tui-{}.txt", std::process::id()));
+        fs::write(&path, (1..=20).map(|number| format!("line {}\n", number)).collect::<String>()).unwrap();
+        let mut browser = Browser::new("line");
+        browser.set_hits(hits(20).into_iter().map(|hit| Hit { path: path.clone(), ..hit }).collect());
+        for _ in 0..6 {
+            browser.handle(Key::Down, 4);
+        }
+
+        // A row for the query, 4 for the list, the separator and 4 for the preview
+        let (rows, cursor) = browser.render(40, 10);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(10, rows.len());
+        assert_eq!(6, cursor);
+        assert!(rows[0].starts_with("> line") && rows[0].ends_with("20 matches"));
+        assert!(rows[1].ends_with(":4:line 4"));
+        assert!(rows[4].contains(":7:line 7") && rows[4].starts_with("\x1b[7m"));
+        assert_eq!(paint("    7 line 7", color::MATCH, true), rows[8]);
+        assert_eq!(format!("{}line 8", paint("    8 ", color::LINE_NUMBER, true)), rows[9]);
+    }
+
+    #[test]
+    fn truncate_replaces_control_characters() {
+        assert_eq!("a b", truncate("a\tbcd", 3));
+        assert_eq!("ßü", truncate("ßü", 10));
+    }
+}
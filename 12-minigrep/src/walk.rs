@@ -0,0 +1,305 @@
+// Recursive directory traversal that honours .gitignore and .ignore files.
+//
+// Every directory can contain ignore files whose rules apply to everything below that directory.
+// Rules are checked in order and the last matching rule wins, so rules from deeper directories
+// (and `.ignore` over `.gitignore` in the same directory) override the ones before them.
+// Ignored directories are never entered, which is also how git behaves. Like git, the ignore files of
+// the directories above the search root apply too, up to the root of the git repository (the
+// directory containing `.git`). Outside a repository, only the ignore files below the root are read.
+// On top of the ignore files, a Filter from the command line can leave out more files.
+// The files of a directory's index (see index) are always left out.
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::error::MinigrepError;
+use crate::filter::Filter;
+use crate::glob::Glob;
+use crate::index;
+
+const IGNORE_FILES: [&str; 2] = [".gitignore", ".ignore"];
+
+#[derive(Debug, Clone)]
+struct Rule {
+    glob: Glob,
+    // Paths are matched relative to this directory, the one containing the ignore file
+    base: PathBuf,
+    // For an ignore file above the search root, the path from its directory to the root, which
+    // is then the base
+    prefix: PathBuf,
+    // A pattern containing a '/' is matched against the relative path, otherwise against the file name
+    anchored: bool,
+    negated: bool,
+    dir_only: bool,
+}
+
+impl Rule {
+    // Parses one line of an ignore file, returns None for blank lines, comments and invalid patterns
+    fn parse(line: &str, base: &Path, prefix: &Path) -> Option<Rule> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        let (negated, pattern) = match line.strip_prefix('!') {
+            Some(pattern) => (true, pattern),
+            None => (false, line.strip_prefix('\\').unwrap_or(line)),
+        };
+        let (dir_only, pattern) = match pattern.strip_suffix('/') {
+            Some(pattern) => (true, pattern),
+            None => (false, pattern),
+        };
+        let anchored = pattern.contains('/');
+        let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+        if pattern.is_empty() {
+            return None;
+        }
+
+        let glob = Glob::new(pattern).ok()?;
+        Some(Rule { glob, base: base.to_path_buf(), prefix: prefix.to_path_buf(), anchored, negated, dir_only })
+    }
+
+    fn matches(&self, path: &Path, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+
+        let relative = match path.strip_prefix(&self.base) {
+            Ok(relative) => relative,
+            Err(_) => return false,
+        };
+        if self.anchored {
+            let relative = self.prefix.join(relative);
+            let relative: Vec<_> = relative.components().map(|c| c.as_os_str().to_string_lossy()).collect();
+            self.glob.is_match(&relative.join("/"))
+        } else {
+            path.file_name().is_some_and(|name| self.glob.is_match(&name.to_string_lossy()))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Ignore {
+    rules: Vec<Rule>,
+}
+
+impl Ignore {
+    // Returns the rules of the directories above `root` in its git repository, outermost first
+    fn above(root: &Path) -> Ignore {
+        let mut ignore = Ignore::default();
+        let absolute = match fs::canonicalize(root) {
+            Ok(absolute) => absolute,
+            Err(_) => return ignore,
+        };
+        let repository = match absolute.ancestors().find(|dir| dir.join(".git").exists()) {
+            Some(repository) if repository != absolute => repository,
+            _ => return ignore,
+        };
+
+        let mut dirs: Vec<&Path> = absolute.ancestors().skip(1).take_while(|dir| dir.starts_with(repository)).collect();
+        dirs.reverse();
+        for dir in dirs {
+            let prefix = absolute.strip_prefix(dir).unwrap_or(&absolute);
+            ignore.read(dir, root, prefix);
+        }
+        ignore
+    }
+
+    // Returns the rules for a directory: the parent's rules followed by the directory's own
+    fn child(&self, dir: &Path) -> Ignore {
+        let mut ignore = self.clone();
+        ignore.read(dir, dir, Path::new(""));
+        ignore
+    }
+
+    // Adds the rules of the ignore files in `dir`, see Rule for `base` and `prefix`
+    fn read(&mut self, dir: &Path, base: &Path, prefix: &Path) {
+        for name in IGNORE_FILES {
+            if let Ok(contents) = fs::read_to_string(dir.join(name)) {
+                self.rules.extend(contents.lines().filter_map(|line| Rule::parse(line, base, prefix)));
+            }
+        }
+    }
+
+    fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        match self.rules.iter().rev().find(|rule| rule.matches(path, is_dir)) {
+            Some(rule) => !rule.negated,
+            None => false,
+        }
+    }
+}
+
+// Returns every file below `root` that is not ignored and passes the filter, sorted by path.
+// Symbolic links are not followed. Directories that can't be read are left out and their errors
+// are added to `errors`, so the rest can still be searched.
+pub fn walk(root: &Path, filter: &Filter, errors: &mut Vec<MinigrepError>) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    walk_dir(root, &Walk { root, filter }, &Ignore::above(root), &mut files, errors);
+    files
+}
+
+// What stays the same during a walk
+struct Walk<'a> {
+    root: &'a Path,
+    filter: &'a Filter,
+}
+
+impl Walk<'_> {
+    // The path below the root, with '/' separators as the filter expects
+    fn relative(&self, path: &Path) -> String {
+        let relative = path.strip_prefix(self.root).unwrap_or(path);
+        let components: Vec<_> = relative.components().map(|c| c.as_os_str().to_string_lossy()).collect();
+        components.join("/")
+    }
+}
+
+fn walk_dir(dir: &Path, walk: &Walk, parent: &Ignore, files: &mut Vec<PathBuf>, errors: &mut Vec<MinigrepError>) {
+    let ignore = parent.child(dir);
+
+    let mut entries = match fs::read_dir(dir).and_then(|entries| entries.collect::<io::Result<Vec<_>>>()) {
+        Ok(entries) => entries,
+        Err(error) => return errors.push(MinigrepError::file(dir, error)),
+    };
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let path = entry.path();
+        let file_type = match entry.file_type() {
+            Ok(file_type) => file_type,
+            Err(error) => {
+                errors.push(MinigrepError::file(&path, error));
+                continue;
+            }
+        };
+
+        if file_type.is_dir() {
+            if entry.file_name() == ".git" || ignore.is_ignored(&path, true) || !walk.filter.is_dir_searched(&walk.relative(&path)) {
+                continue;
+            }
+            walk_dir(&path, walk, &ignore, files, errors);
+        } else if file_type.is_file()
+            && !index::is_index_file(&entry.file_name())
+            && !ignore.is_ignored(&path, false) && walk.filter.is_file_searched(&walk.relative(&path)) {
+            files.push(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn create_tree(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let root = env::temp_dir().join(format!("minigrep-walk-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        for (path, contents) in files {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+        root
+    }
+
+    fn relative_files(root: &Path) -> Vec<String> {
+        filtered_files(root, &Filter::default())
+    }
+
+    fn filtered_files(root: &Path, filter: &Filter) -> Vec<String> {
+        let mut errors = Vec::new();
+        let files = walk(root, filter, &mut errors);
+        assert!(errors.is_empty());
+        files.iter()
+            .map(|path| path.strip_prefix(root).unwrap().to_string_lossy().replace('\\', "/"))
+            .collect()
+    }
+
+    #[test]
+    fn walk_lists_files_recursively_in_order() {
+        let root = create_tree("recursive", &[("b.txt", ""), ("a/c.txt", ""), ("a/b/d.txt", "")]);
+        assert_eq!(vec!["a/b/d.txt", "a/c.txt", "b.txt"], relative_files(&root));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn walk_honours_gitignore() {
+        let root = create_tree("gitignore", &[
+            (".gitignore", "# build output\ntarget/\n*.log\n!keep.log\n/root.txt\n"),
+            ("target/debug/minigrep", ""),
+            ("src/target", ""),
+            ("src/main.rs", ""),
+            ("src/debug.log", ""),
+            ("src/keep.log", ""),
+            ("root.txt", ""),
+            ("src/root.txt", ""),
+        ]);
+        assert_eq!(vec![".gitignore", "src/keep.log", "src/main.rs", "src/root.txt", "src/target"], relative_files(&root));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn nested_ignore_files_override_parents() {
+        let root = create_tree("nested", &[
+            (".gitignore", "*.txt\n"),
+            ("a.txt", ""),
+            ("docs/.ignore", "!*.txt\nsecret/\n"),
+            ("docs/b.txt", ""),
+            ("docs/secret/c.md", ""),
+        ]);
+        assert_eq!(vec![".gitignore", "docs/.ignore", "docs/b.txt"], relative_files(&root));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn ignore_files_above_the_root_apply_up_to_the_repository() {
+        let root = create_tree("above", &[
+            (".gitignore", "*.log\n/sub/build/\n"),
+            ("sub/.gitignore", "*.tmp\n"),
+            ("sub/a.txt", ""),
+            ("sub/x.log", ""),
+            ("sub/deeper/y.tmp", ""),
+            ("sub/deeper/z.txt", ""),
+            ("sub/build/b.txt", ""),
+            ("sub/deeper/build/c.txt", ""),
+        ]);
+        // Not a repository yet, so the root's own ignore files are the first ones read
+        assert_eq!(vec![".gitignore", "a.txt", "build/b.txt", "deeper/build/c.txt", "deeper/z.txt", "x.log"], relative_files(&root.join("sub")));
+        assert_eq!(vec!["build/c.txt", "y.tmp", "z.txt"], relative_files(&root.join("sub/deeper")));
+
+        fs::create_dir(root.join(".git")).unwrap();
+        assert_eq!(vec![".gitignore", "a.txt", "deeper/build/c.txt", "deeper/z.txt"], relative_files(&root.join("sub")));
+        assert_eq!(vec!["build/c.txt", "z.txt"], relative_files(&root.join("sub/deeper")));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn walk_skips_git_directory() {
+        let root = create_tree("git", &[(".git/HEAD", ""), ("a.txt", "")]);
+        assert_eq!(vec!["a.txt"], relative_files(&root));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn walk_applies_filter() {
+        let root = create_tree("filter", &[
+            ("Cargo.toml", ""),
+            ("README.md", ""),
+            ("src/main.rs", ""),
+            ("src/notes.md", ""),
+            ("target/debug/build.rs", ""),
+        ]);
+        let filter = Filter::new(&[String::from("rust"), String::from("toml")], &[], &[String::from("!target/**")]).unwrap();
+        assert_eq!(vec!["Cargo.toml", "src/main.rs"], filtered_files(&root, &filter));
+        let filter = Filter::new(&[], &[String::from("md")], &[]).unwrap();
+        assert_eq!(vec!["Cargo.toml", "src/main.rs", "target/debug/build.rs"], filtered_files(&root, &filter));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn walk_reports_unreadable_directories() {
+        let mut errors = Vec::new();
+        let root = env::temp_dir().join(format!("minigrep-walk-missing-{}", std::process::id()));
+        assert!(walk(&root, &Filter::default(), &mut errors).is_empty());
+        assert!(matches!(&errors[..], [MinigrepError::Io { path: Some(path), .. }] if *path == root));
+    }
+}